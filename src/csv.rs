@@ -0,0 +1,68 @@
+pub fn cell(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn row<'a, I>(values: I) -> String
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let cells: Vec<String> = values.into_iter().map(cell).collect();
+
+    cells.join(",")
+}
+
+pub fn parse(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => cell.push(c),
+            (false, '"') => quoted = true,
+            (false, ',') => row.push(std::mem::take(&mut cell)),
+            (false, '\r') => {}
+            (false, '\n') => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            }
+            (false, c) => cell.push(c),
+        }
+    }
+
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_rows() {
+        let text = format!(
+            "{}\r\n{}\n",
+            row(vec!["a", "b,c"]),
+            row(vec!["say \"hi\"", ""])
+        );
+
+        assert_eq!(text, "a,\"b,c\"\r\n\"say \"\"hi\"\"\",\n");
+        assert_eq!(parse(&text), vec![vec!["a", "b,c"], vec!["say \"hi\"", ""]]);
+    }
+}
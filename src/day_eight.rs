@@ -1,157 +1,1727 @@
-use super::utils::start_day;
-use std::collections::HashSet;
+use super::utils::{option, read_input_arg, start_day};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{self, Write as _};
+use std::io::{self, BufRead, Write};
+use tinyvec::ArrayVec;
 
 pub fn main() {
     let input = start_day("eight");
 
     let code = Code::parse(&input);
 
-    println!("Part one: {}", part_one(&code));
-    println!("Part two: {}", part_two(&code));
+    match part_one(&code) {
+        Ok(acc) => println!("Part one: {}", acc),
+        Err(run) => println!("Part one: no loop, {}", run),
+    }
+
+    match part_two(&code) {
+        Ok(acc) => println!("Part two: {}", acc),
+        Err(e) => println!("Part two: {}", e),
+    }
+
     println!();
 }
 
-fn part_one(code: &Code) -> isize {
-    let mut already_visited = HashSet::new();
+pub fn debug(args: &[String]) {
+    let input = read_input_arg(args, "eight");
+
+    let code = Code::parse(input.trim());
+    let mut debugger = Debugger::new(&code);
+
+    print!("{}", debugger.listing(2));
+
+    let stdin = io::stdin();
+
+    loop {
+        print!("(handheld) ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+
+        if stdin.lock().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+
+        match line.trim() {
+            "q" | "quit" => break,
+            command => print!("{}", debugger.command(command)),
+        }
+    }
+}
+
+pub fn repair(args: &[String]) {
+    let text = read_input_arg(args, "eight");
+
+    match repairs(&Code::parse(text.trim())) {
+        Ok(repairs) => {
+            for repair in repairs {
+                println!("{}", repair);
+            }
+        }
+        Err(e) => println!("{}", e),
+    }
+}
+
+pub fn cfg(args: &[String]) {
+    let text = read_input_arg(args, "eight");
+
+    let code = Code::parse(text.trim());
+    let cfg = Cfg::of(&code);
+
+    if args.iter().any(|a| a == "--dot") {
+        print!("{}", cfg.to_dot());
+    } else {
+        print!("{}", cfg.to_text());
+    }
+}
+
+pub fn assemble(args: &[String]) {
+    let source = read_input_arg(args, "eight");
+
+    match InstrSet::standard().assemble(&source) {
+        Ok(code) => println!("{}", code),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn disassemble(args: &[String]) {
+    let text = read_input_arg(args, "eight");
+
+    print!("{}", Code::parse(text.trim()).disassemble());
+}
+
+pub fn run(args: &[String]) {
+    let text = read_input_arg(args, "eight");
+
+    let code = match InstrSet::standard().parse(text.trim()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let max_steps = option(args, "max-steps").map_or(1_000_000, |x| x.parse().unwrap());
+
+    let mut program = code.new_program();
+
+    if let Some(input) = option(args, "input") {
+        program.machine.input = input
+            .split(',')
+            .map(|x| x.trim().parse().unwrap())
+            .collect();
+    }
+
+    println!("{}", program.run(max_steps));
+
+    let registers: Vec<String> = REGISTERS
+        .iter()
+        .zip(&program.machine.registers)
+        .map(|(name, value)| format!("{} = {}", name, value))
+        .collect();
+
+    println!("Registers: {}", registers.join(", "));
+
+    if !program.machine.output.is_empty() {
+        let output: Vec<String> = program
+            .machine
+            .output
+            .iter()
+            .map(|x| x.to_string())
+            .collect();
+
+        println!("Output: {}", output.join(","));
+    }
+}
+
+fn part_one(code: &Code) -> Result<isize, Run> {
+    let run = code.new_program().run(1_000_000);
+
+    match run.outcome {
+        Outcome::InfiniteLoop(_) => Ok(run.acc),
+        _ => Err(run),
+    }
+}
+
+fn part_two(code: &Code) -> Result<isize, RepairError> {
+    repairs(code).map(|repairs| repairs[0].acc)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Repair {
+    index: usize,
+    from: Instr,
+    to: Instr,
+    acc: isize,
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "instruction {}: {} -> {}, acc = {}",
+            self.index, self.from, self.to, self.acc
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RepairError {
+    AlreadyTerminates(isize),
+    NoFix,
+    Unsupported(usize, String),
+}
+
+impl fmt::Display for RepairError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RepairError::AlreadyTerminates(acc) => {
+                write!(f, "the program already terminates, with acc = {}", acc)
+            }
+            RepairError::NoFix => write!(f, "no single nop/jmp flip makes the program terminate"),
+            RepairError::Unsupported(index, instr) => write!(
+                f,
+                "instruction {}: cannot repair {}, only nop, acc and jmp with constant operands are supported",
+                index, instr
+            ),
+        }
+    }
+}
+
+fn repairs(code: &Code) -> Result<Vec<Repair>, RepairError> {
+    let end = code.0.len();
+
+    let mut offsets = Vec::with_capacity(end);
+    let mut deltas = Vec::with_capacity(end);
+
+    for (i, instr) in code.0.iter().enumerate() {
+        let (offset, delta) = match (instr.name(), instr.args.as_slice()) {
+            ("nop", [Operand::Value(_)]) => (1, 0),
+            ("acc", [Operand::Value(x)]) => (1, *x),
+            ("jmp", [Operand::Value(x)]) => (*x, 0),
+            _ => return Err(RepairError::Unsupported(i, instr.to_string())),
+        };
+
+        offsets.push(offset);
+        deltas.push(delta);
+    }
+
+    let target = |i: usize, offset: isize| {
+        let target = (i as isize).checked_add(offset)?;
+
+        if (0..=end as isize).contains(&target) {
+            Some(target as usize)
+        } else {
+            None
+        }
+    };
+
+    let mut sources = vec![Vec::new(); end + 1];
+
+    for (i, &offset) in offsets.iter().enumerate() {
+        if let Some(t) = target(i, offset) {
+            sources[t].push(i);
+        }
+    }
+
+    let mut acc_to_end: Vec<Option<isize>> = vec![None; end + 1];
+    acc_to_end[end] = Some(0);
+
+    let mut to_visit = vec![end];
+
+    while let Some(t) = to_visit.pop() {
+        for &i in &sources[t] {
+            acc_to_end[i] = acc_to_end[t].map(|acc| acc.wrapping_add(deltas[i]));
+            to_visit.push(i);
+        }
+    }
+
+    if let Some(acc) = acc_to_end[0] {
+        return Err(RepairError::AlreadyTerminates(acc));
+    }
+
+    let set = InstrSet::standard();
+
+    let mut repairs = Vec::new();
+    let mut visited = vec![false; end];
+    let mut acc: isize = 0;
+    let mut pos = 0;
+
+    while pos < end && !visited[pos] {
+        visited[pos] = true;
+
+        let from = &code.0[pos];
+
+        let flipped = match (from.name(), from.args.as_slice()) {
+            ("nop", &[Operand::Value(x)]) => Some(("jmp", x)),
+            ("jmp", _) => Some(("nop", 1)),
+            _ => None,
+        };
+
+        if let Some((name, offset)) = flipped {
+            let rest = target(pos, offset).and_then(|t| acc_to_end[t]);
+
+            if let Some(rest) = rest {
+                let mut to = from.clone();
+                to.op = set.opcode(name).unwrap();
+
+                repairs.push(Repair {
+                    index: pos,
+                    from: from.clone(),
+                    to,
+                    acc: acc.wrapping_add(rest),
+                });
+            }
+        }
+
+        acc = acc.wrapping_add(deltas[pos]);
+
+        match target(pos, offsets[pos]) {
+            Some(t) => pos = t,
+            None => break,
+        }
+    }
+
+    if repairs.is_empty() {
+        Err(RepairError::NoFix)
+    } else {
+        Ok(repairs)
+    }
+}
+
+const REGISTERS: [&str; 5] = ["acc", "a", "b", "c", "d"];
+
+type Exec = fn(&mut Machine, &[Operand]) -> Flow;
+
+type Operands = ArrayVec<[Operand; 3]>;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum OperandKind {
+    Register,
+    Value,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operand {
+    Value(isize),
+    Register(usize),
+}
+
+impl Default for Operand {
+    fn default() -> Self {
+        Operand::Value(0)
+    }
+}
+
+impl Operand {
+    pub fn parse(s: &str, kind: OperandKind) -> Option<Self> {
+        if let Some(register) = REGISTERS.iter().position(|&r| r == s) {
+            return Some(Operand::Register(register));
+        }
+
+        match kind {
+            OperandKind::Register => None,
+            OperandKind::Value => s.parse().ok().map(Operand::Value),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Value(x) => write!(f, "{:+}", x),
+            Operand::Register(register) => write!(f, "{}", REGISTERS[*register]),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Flow {
+    Next,
+    Jump(isize),
+    Halt,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Control {
+    Next,
+    Jump,
+    Branch,
+    Halt,
+    MayHalt,
+}
+
+#[derive(Copy, Clone)]
+struct Opcode {
+    name: &'static str,
+    operands: &'static [OperandKind],
+    control: Control,
+    exec: Exec,
+}
+
+impl fmt::Debug for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl PartialEq for Opcode {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for Opcode {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Instr {
+    op: Opcode,
+    args: Operands,
+}
+
+impl Instr {
+    pub fn name(&self) -> &'static str {
+        self.op.name
+    }
+
+    pub fn has_static_flow(&self) -> bool {
+        match self.op.control {
+            Control::Next | Control::Halt => true,
+            Control::Jump => matches!(self.args.last(), Some(Operand::Value(_))),
+            Control::Branch | Control::MayHalt => false,
+        }
+    }
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())?;
+
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ParseError {
+    line: usize,
+    message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct InstrSet {
+    opcodes: HashMap<&'static str, Opcode>,
+}
+
+impl InstrSet {
+    pub fn standard() -> Self {
+        use OperandKind::{Register, Value};
+
+        let mut set = InstrSet {
+            opcodes: HashMap::new(),
+        };
+
+        set.register("nop", &[Value], Control::Next, |_, _| Flow::Next);
+        set.register("acc", &[Value], Control::Next, |m, args| {
+            m.registers[0] = m.registers[0].wrapping_add(m.get(args[0]));
+            Flow::Next
+        });
+        set.register("jmp", &[Value], Control::Jump, |m, args| {
+            Flow::Jump(m.get(args[0]))
+        });
+
+        set.register("set", &[Register, Value], Control::Next, |m, args| {
+            m.set(args[0], m.get(args[1]));
+            Flow::Next
+        });
+        set.register("add", &[Register, Value], Control::Next, |m, args| {
+            m.set(args[0], m.get(args[0]).wrapping_add(m.get(args[1])));
+            Flow::Next
+        });
+        set.register("sub", &[Register, Value], Control::Next, |m, args| {
+            m.set(args[0], m.get(args[0]).wrapping_sub(m.get(args[1])));
+            Flow::Next
+        });
+        set.register("mul", &[Register, Value], Control::Next, |m, args| {
+            m.set(args[0], m.get(args[0]).wrapping_mul(m.get(args[1])));
+            Flow::Next
+        });
+
+        set.register("jz", &[Value, Value], Control::Branch, |m, args| {
+            m.jump_if(m.get(args[0]) == 0, args[1])
+        });
+        set.register("jnz", &[Value, Value], Control::Branch, |m, args| {
+            m.jump_if(m.get(args[0]) != 0, args[1])
+        });
+        set.register("jlt", &[Value, Value], Control::Branch, |m, args| {
+            m.jump_if(m.get(args[0]) < 0, args[1])
+        });
+        set.register("jgt", &[Value, Value], Control::Branch, |m, args| {
+            m.jump_if(m.get(args[0]) > 0, args[1])
+        });
+
+        set.register("hlt", &[], Control::Halt, |_, _| Flow::Halt);
+
+        set.register("inp", &[Register], Control::MayHalt, |m, args| {
+            match m.input.pop_front() {
+                Some(x) => {
+                    m.set(args[0], x);
+                    Flow::Next
+                }
+                None => Flow::Halt,
+            }
+        });
+        set.register("out", &[Value], Control::Next, |m, args| {
+            let x = m.get(args[0]);
+            m.output.push(x);
+            Flow::Next
+        });
+
+        set
+    }
+
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        operands: &'static [OperandKind],
+        control: Control,
+        exec: Exec,
+    ) {
+        assert!(
+            operands.len() <= 3,
+            "{} takes more than three operands",
+            name
+        );
+
+        self.opcodes.insert(
+            name,
+            Opcode {
+                name,
+                operands,
+                control,
+                exec,
+            },
+        );
+    }
+
+    pub fn opcode(&self, name: &str) -> Option<Opcode> {
+        self.opcodes.get(name).copied()
+    }
+
+    pub fn parse_instr(&self, text: &str) -> Result<Instr, String> {
+        let mut words = text.split_whitespace();
+
+        let name = words.next().ok_or("missing instruction")?;
+        let op = self
+            .opcode(name)
+            .ok_or_else(|| format!("unknown instruction {:?}", name))?;
+
+        let words: Vec<&str> = words.collect();
+
+        if words.len() != op.operands.len() {
+            return Err(format!(
+                "{} takes {} operands, found {}",
+                name,
+                op.operands.len(),
+                words.len()
+            ));
+        }
+
+        let args = words
+            .iter()
+            .zip(op.operands)
+            .map(|(word, &kind)| {
+                Operand::parse(word, kind)
+                    .ok_or_else(|| format!("{:?} is not a valid operand", word))
+            })
+            .collect::<Result<Operands, String>>()?;
+
+        Ok(Instr { op, args })
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Code, ParseError> {
+        let code = input
+            .lines()
+            .enumerate()
+            .map(|(i, l)| {
+                self.parse_instr(l).map_err(|message| ParseError {
+                    line: i + 1,
+                    message,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Code(code))
+    }
+
+    pub fn assemble(&self, source: &str) -> Result<Code, ParseError> {
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        let mut lines = Vec::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let error = |message| ParseError {
+                line: i + 1,
+                message,
+            };
+
+            let mut text = line.split(';').next().unwrap().trim();
+
+            while let Some((label, rest)) = text.split_once(':') {
+                let label = label.trim();
+
+                if !is_label(label) {
+                    return Err(error(format!("{:?} is not a valid label", label)));
+                }
+
+                if labels.insert(label, lines.len()).is_some() {
+                    return Err(error(format!("label {} is already defined", label)));
+                }
+
+                text = rest.trim();
+            }
+
+            if !text.is_empty() {
+                lines.push((i + 1, text));
+            }
+        }
+
+        let code = lines
+            .iter()
+            .enumerate()
+            .map(|(index, &(line, text))| {
+                let mut words: Vec<String> = text.split_whitespace().map(String::from).collect();
+
+                let jumps = words
+                    .first()
+                    .and_then(|name| self.opcode(name))
+                    .is_some_and(|op| matches!(op.control, Control::Jump | Control::Branch));
+
+                if let Some(last) = words.last_mut().filter(|last| jumps && is_label(last)) {
+                    match labels.get(last.as_str()) {
+                        Some(&target) => *last = format!("{:+}", target as isize - index as isize),
+                        None => {
+                            return Err(ParseError {
+                                line,
+                                message: format!("undefined label {}", last),
+                            })
+                        }
+                    }
+                }
+
+                self.parse_instr(&words.join(" "))
+                    .map_err(|message| ParseError { line, message })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Code(code))
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !REGISTERS.contains(&s)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Code(Vec<Instr>);
+
+impl Code {
+    pub fn parse(input: &str) -> Self {
+        InstrSet::standard()
+            .parse(input)
+            .unwrap_or_else(|e| panic!("Invalid boot code: {}", e))
+    }
+
+    pub fn new_program(&self) -> Program {
+        Program {
+            code: self,
+            pos: 0,
+            machine: Machine::default(),
+            status: Status::Running,
+        }
+    }
+
+    pub fn disassemble(&self) -> String {
+        let len = self.0.len() as isize;
+
+        let target = |i: usize, instr: &Instr| match (instr.op.control, instr.args.last()) {
+            (Control::Jump | Control::Branch, Some(&Operand::Value(x))) => (i as isize)
+                .checked_add(x)
+                .filter(|t| (0..=len).contains(t)),
+            _ => None,
+        };
+
+        let labels: HashMap<isize, String> = self
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, instr)| target(i, instr))
+            .collect::<BTreeSet<isize>>()
+            .into_iter()
+            .enumerate()
+            .map(|(n, t)| (t, format!("L{}", n + 1)))
+            .collect();
+
+        let mut out = String::new();
+
+        for (i, instr) in self.0.iter().enumerate() {
+            if let Some(label) = labels.get(&(i as isize)) {
+                writeln!(out, "{}:", label).unwrap();
+            }
+
+            write!(out, "    {}", instr.name()).unwrap();
+
+            for (n, arg) in instr.args.iter().enumerate() {
+                match target(i, instr).filter(|_| n + 1 == instr.args.len()) {
+                    Some(t) => write!(out, " {}", labels[&t]).unwrap(),
+                    None => write!(out, " {}", arg).unwrap(),
+                }
+            }
+
+            out.push('\n');
+        }
+
+        if let Some(label) = labels.get(&len) {
+            writeln!(out, "{}:", label).unwrap();
+        }
+
+        out
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines: Vec<String> = self.0.iter().map(|instr| instr.to_string()).collect();
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Machine {
+    registers: [isize; 5],
+    input: VecDeque<isize>,
+    output: Vec<isize>,
+}
+
+impl Machine {
+    pub fn get(&self, operand: Operand) -> isize {
+        match operand {
+            Operand::Value(x) => x,
+            Operand::Register(register) => self.registers[register],
+        }
+    }
+
+    pub fn set(&mut self, operand: Operand, value: isize) {
+        if let Operand::Register(register) = operand {
+            self.registers[register] = value;
+        }
+    }
+
+    fn jump_if(&self, condition: bool, offset: Operand) -> Flow {
+        if condition {
+            Flow::Jump(self.get(offset))
+        } else {
+            Flow::Next
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Program<'a> {
+    code: &'a Code,
+    pos: usize,
+    machine: Machine,
+    status: Status,
+}
+
+impl<'a> Program<'a> {
+    pub fn acc(&self) -> isize {
+        self.machine.registers[0]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.status != Status::Running || self.pos >= self.code.0.len()
+    }
+}
+
+impl<'a> Iterator for Program<'a> {
+    type Item = (usize, isize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.status != Status::Running {
+            return None;
+        }
+
+        let instr = self.code.0.get(self.pos)?;
+
+        match (instr.op.exec)(&mut self.machine, &instr.args) {
+            Flow::Next => {
+                self.pos += 1;
+            }
+            Flow::Jump(x) => {
+                let target = (self.pos as isize).saturating_add(x);
+
+                if (0..=self.code.0.len() as isize).contains(&target) {
+                    self.pos = target as usize;
+                } else {
+                    self.status = Status::OutOfBounds(target);
+                }
+            }
+            Flow::Halt => {
+                self.status = Status::Halted;
+            }
+        };
+
+        Some((self.pos, self.acc()))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Status {
+    Running,
+    Halted,
+    OutOfBounds(isize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Terminated,
+    OutOfBounds { from: usize, target: isize },
+    InfiniteLoop(BTreeSet<usize>),
+    StepLimit,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Terminated => write!(f, "terminated normally"),
+            Outcome::OutOfBounds { from, target } => write!(
+                f,
+                "jumped out of bounds from instruction {} to {}, {}",
+                from,
+                target,
+                if *target < 0 {
+                    "before the start"
+                } else {
+                    "past the end"
+                }
+            ),
+            Outcome::InfiniteLoop(instructions) => {
+                let instructions: Vec<String> =
+                    instructions.iter().map(|i| i.to_string()).collect();
+
+                write!(
+                    f,
+                    "infinite loop through instructions {}",
+                    instructions.join(", ")
+                )
+            }
+            Outcome::StepLimit => write!(f, "step limit exceeded"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Run {
+    outcome: Outcome,
+    acc: isize,
+    steps: usize,
+}
+
+impl fmt::Display for Run {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} after {} steps, acc = {}",
+            self.outcome, self.steps, self.acc
+        )
+    }
+}
+
+impl<'a> Program<'a> {
+    pub fn run(&mut self, max_steps: usize) -> Run {
+        let static_flow = self.code.0.iter().all(Instr::has_static_flow);
+
+        let mut trace = Vec::new();
+        let mut first_visits = HashMap::new();
+        let mut snapshot = None;
+        let mut power = 1;
+
+        let outcome = loop {
+            match self.status {
+                Status::Halted => break Outcome::Terminated,
+                Status::OutOfBounds(target) => {
+                    break Outcome::OutOfBounds {
+                        from: self.pos,
+                        target,
+                    }
+                }
+                Status::Running if self.pos >= self.code.0.len() => break Outcome::Terminated,
+                Status::Running => {}
+            }
+
+            if trace.len() == max_steps {
+                break Outcome::StepLimit;
+            }
+
+            if static_flow {
+                if let Some(&first) = first_visits.get(&self.pos) {
+                    break Outcome::InfiniteLoop(trace[first..].iter().copied().collect());
+                }
+
+                first_visits.insert(self.pos, trace.len());
+            } else {
+                let state = (self.pos, self.machine.registers, self.machine.input.len());
+
+                match snapshot {
+                    Some((ref snapshot_state, start)) if *snapshot_state == state => {
+                        break Outcome::InfiniteLoop(trace[start..].iter().copied().collect());
+                    }
+                    Some((_, start)) if trace.len() - start < power => {}
+                    _ => {
+                        snapshot = Some((state, trace.len()));
+                        power *= 2;
+                    }
+                }
+            }
+
+            trace.push(self.pos);
+            self.next();
+        };
+
+        Run {
+            outcome,
+            acc: self.acc(),
+            steps: trace.len(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Target {
+    Block(usize),
+    End,
+    OutOfBounds(isize),
+    Dynamic,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Block(block) => write!(f, "block {}", block),
+            Target::End => write!(f, "end"),
+            Target::OutOfBounds(target) => write!(f, "out of bounds ({})", target),
+            Target::Dynamic => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Block {
+    start: usize,
+    end: usize,
+    targets: Vec<Target>,
+    acc_delta: Option<isize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Prediction {
+    Terminates,
+    OutOfBounds(isize),
+    Loops(Vec<usize>),
+    MayLoop(Vec<Vec<usize>>),
+    NoLoops,
+}
+
+impl fmt::Display for Prediction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let blocks = |blocks: &[usize]| {
+            blocks
+                .iter()
+                .map(|b| b.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match self {
+            Prediction::Terminates => write!(f, "terminates"),
+            Prediction::OutOfBounds(target) => write!(f, "jumps out of bounds to {}", target),
+            Prediction::Loops(cycle) => {
+                write!(f, "infinite loop through blocks {}", blocks(cycle))
+            }
+            Prediction::MayLoop(loops) => {
+                let loops: Vec<String> = loops.iter().map(|l| blocks(l)).collect();
+
+                write!(f, "may loop through blocks {}", loops.join(" or "))
+            }
+            Prediction::NoLoops => write!(f, "cannot loop"),
+        }
+    }
+}
+
+struct Cfg<'a> {
+    code: &'a Code,
+    blocks: Vec<Block>,
+    reachable: Vec<bool>,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a> Cfg<'a> {
+    pub fn of(code: &'a Code) -> Self {
+        let len = code.0.len();
+
+        let jump_target = |i: usize, instr: &Instr| match instr.args.last() {
+            Some(&Operand::Value(x)) => Some((i as isize).saturating_add(x)),
+            _ => None,
+        };
+
+        let mut leaders = BTreeSet::new();
+
+        if len > 0 {
+            leaders.insert(0);
+        }
+
+        for (i, instr) in code.0.iter().enumerate() {
+            if instr.op.control == Control::Next {
+                continue;
+            }
+
+            if i + 1 < len {
+                leaders.insert(i + 1);
+            }
+
+            if let Control::Jump | Control::Branch = instr.op.control {
+                if let Some(target) = jump_target(i, instr).filter(|&t| t >= 0 && t < len as isize)
+                {
+                    leaders.insert(target as usize);
+                }
+            }
+        }
+
+        let leaders: Vec<usize> = leaders.into_iter().collect();
+
+        let mut block_of = vec![0; len];
+
+        for (b, &start) in leaders.iter().enumerate() {
+            let end = leaders.get(b + 1).map_or(len, |&next| next);
+
+            for block in &mut block_of[start..end] {
+                *block = b;
+            }
+        }
+
+        let target = |pos: isize| {
+            if pos == len as isize {
+                Target::End
+            } else if pos >= 0 && pos < len as isize {
+                Target::Block(block_of[pos as usize])
+            } else {
+                Target::OutOfBounds(pos)
+            }
+        };
+
+        let blocks: Vec<Block> = leaders
+            .iter()
+            .enumerate()
+            .map(|(b, &start)| {
+                let end = leaders.get(b + 1).map_or(len, |&next| next) - 1;
+                let last = &code.0[end];
+
+                let fall = target(end as isize + 1);
+                let jump = jump_target(end, last).map_or(Target::Dynamic, target);
+
+                let mut targets = match last.op.control {
+                    Control::Next => vec![fall],
+                    Control::Jump => vec![jump],
+                    Control::Branch => vec![fall, jump],
+                    Control::Halt => vec![Target::End],
+                    Control::MayHalt => vec![fall, Target::End],
+                };
+
+                targets.dedup();
+
+                let acc_delta = code.0[start..=end].iter().try_fold(0, |delta, instr| {
+                    let writes_acc =
+                        instr
+                            .op
+                            .operands
+                            .iter()
+                            .zip(&instr.args)
+                            .any(|(&kind, &arg)| {
+                                kind == OperandKind::Register && arg == Operand::Register(0)
+                            });
+
+                    match (instr.name(), instr.args.as_slice()) {
+                        ("acc", &[Operand::Value(x)]) => Some(isize::wrapping_add(delta, x)),
+                        ("acc", _) => None,
+                        _ if writes_acc => None,
+                        _ => Some(delta),
+                    }
+                });
+
+                Block {
+                    start,
+                    end,
+                    targets,
+                    acc_delta,
+                }
+            })
+            .collect();
+
+        let mut cfg = Cfg {
+            code,
+            reachable: vec![false; blocks.len()],
+            blocks,
+            components: Vec::new(),
+        };
+
+        cfg.find_reachable();
+        cfg.find_components();
+
+        cfg
+    }
+
+    fn successors(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+        self.blocks[block]
+            .targets
+            .iter()
+            .flat_map(move |&target| match target {
+                Target::Block(b) => b..b + 1,
+                Target::Dynamic => 0..self.blocks.len(),
+                _ => 0..0,
+            })
+    }
+
+    fn find_reachable(&mut self) {
+        let mut to_visit = Vec::new();
+
+        if !self.blocks.is_empty() {
+            self.reachable[0] = true;
+            to_visit.push(0);
+        }
+
+        while let Some(block) = to_visit.pop() {
+            let successors: Vec<usize> = self.successors(block).collect();
+
+            for next in successors {
+                if !self.reachable[next] {
+                    self.reachable[next] = true;
+                    to_visit.push(next);
+                }
+            }
+        }
+    }
+
+    fn find_components(&mut self) {
+        let mut tarjan = Tarjan {
+            index: vec![None; self.blocks.len()],
+            low_link: vec![0; self.blocks.len()],
+            on_stack: vec![false; self.blocks.len()],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        };
+
+        for block in 0..self.blocks.len() {
+            if tarjan.index[block].is_none() {
+                tarjan.visit(self, block);
+            }
+        }
+
+        for component in &mut tarjan.components {
+            component.sort_unstable();
+        }
+
+        tarjan.components.sort();
+
+        self.components = tarjan.components;
+    }
+
+    pub fn unreachable(&self) -> Vec<usize> {
+        self.blocks
+            .iter()
+            .zip(&self.reachable)
+            .filter(|(_, &reachable)| !reachable)
+            .flat_map(|(block, _)| block.start..=block.end)
+            .collect()
+    }
+
+    pub fn loops(&self) -> Vec<&[usize]> {
+        self.components
+            .iter()
+            .filter(|component| {
+                component.len() > 1 || self.successors(component[0]).any(|b| b == component[0])
+            })
+            .map(Vec::as_slice)
+            .collect()
+    }
+
+    pub fn instructions(&self, blocks: &[usize]) -> BTreeSet<usize> {
+        blocks
+            .iter()
+            .flat_map(|&b| self.blocks[b].start..=self.blocks[b].end)
+            .collect()
+    }
+
+    pub fn predict(&self) -> Prediction {
+        let mut path = Vec::new();
+        let mut block = 0;
+
+        if self.blocks.is_empty() {
+            return Prediction::Terminates;
+        }
+
+        loop {
+            if let Some(start) = path.iter().position(|&b| b == block) {
+                return Prediction::Loops(path[start..].to_vec());
+            }
+
+            path.push(block);
+
+            match self.blocks[block].targets.as_slice() {
+                [Target::Block(next)] => block = *next,
+                [Target::End] => return Prediction::Terminates,
+                [Target::OutOfBounds(target)] => return Prediction::OutOfBounds(*target),
+                _ => break,
+            }
+        }
+
+        let loops: Vec<Vec<usize>> = self
+            .loops()
+            .into_iter()
+            .filter(|component| self.reachable[component[0]])
+            .map(|component| component.to_vec())
+            .collect();
+
+        if loops.is_empty() {
+            Prediction::NoLoops
+        } else {
+            Prediction::MayLoop(loops)
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        for (b, block) in self.blocks.iter().enumerate() {
+            let delta = block
+                .acc_delta
+                .map_or("?".to_string(), |delta| format!("{:+}", delta));
+
+            writeln!(
+                out,
+                "block {}: instructions {}-{}, acc {}{}",
+                b,
+                block.start,
+                block.end,
+                delta,
+                if self.reachable[b] {
+                    ""
+                } else {
+                    ", unreachable"
+                }
+            )
+            .unwrap();
+
+            for i in block.start..=block.end {
+                writeln!(out, "    {:>4}  {}", i, self.code.0[i]).unwrap();
+            }
+
+            let targets: Vec<String> = block.targets.iter().map(|t| t.to_string()).collect();
+
+            writeln!(out, "    -> {}", targets.join(", ")).unwrap();
+        }
+
+        for component in self.loops() {
+            let blocks: Vec<String> = component.iter().map(|b| b.to_string()).collect();
+
+            writeln!(
+                out,
+                "loop: blocks {} ({} instructions)",
+                blocks.join(", "),
+                self.instructions(component).len()
+            )
+            .unwrap();
+        }
+
+        let unreachable = self.unreachable();
+
+        if !unreachable.is_empty() {
+            writeln!(out, "unreachable: {} instructions", unreachable.len()).unwrap();
+        }
+
+        writeln!(out, "prediction: {}", self.predict()).unwrap();
+
+        out
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+
+        let in_loop: HashSet<usize> = self.loops().into_iter().flatten().copied().collect();
+
+        for (b, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
 
-    let mut prev_acc = 0;
+            for i in block.start..=block.end {
+                write!(label, "{}  {}\\l", i, self.code.0[i]).unwrap();
+            }
+
+            let mut attributes = vec![format!("label=\"{}\"", label)];
+
+            if !self.reachable[b] {
+                attributes.push("style=dashed".to_string());
+            }
+
+            if in_loop.contains(&b) {
+                attributes.push("color=red".to_string());
+            }
+
+            writeln!(out, "    b{} [{}];", b, attributes.join(", ")).unwrap();
+        }
 
-    for (index, acc) in code.new_program() {
-        if !already_visited.insert(index) {
-            return prev_acc;
+        writeln!(out, "    end [shape=doublecircle];").unwrap();
+
+        for (b, block) in self.blocks.iter().enumerate() {
+            for target in &block.targets {
+                match target {
+                    Target::Block(next) => writeln!(out, "    b{} -> b{};", b, next),
+                    Target::End => writeln!(out, "    b{} -> end;", b),
+                    Target::OutOfBounds(pos) => writeln!(
+                        out,
+                        "    oob{} [shape=octagon, label=\"{}\"];\n    b{} -> oob{};",
+                        b, pos, b, b
+                    ),
+                    Target::Dynamic => writeln!(
+                        out,
+                        "    unknown{} [shape=diamond, label=\"?\"];\n    b{} -> unknown{};",
+                        b, b, b
+                    ),
+                }
+                .unwrap();
+            }
         }
 
-        prev_acc = acc;
+        out.push_str("}\n");
+
+        out
     }
+}
 
-    panic!("Solution not found");
+struct Tarjan {
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
 }
 
-fn part_two(code: &Code) -> isize {
-    let mut i = 0;
+impl Tarjan {
+    fn visit(&mut self, cfg: &Cfg, block: usize) {
+        self.index[block] = Some(self.next_index);
+        self.low_link[block] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(block);
+        self.on_stack[block] = true;
 
-    loop {
-        let mut new_code = code.clone();
-        i = new_code.mutate_instruction(i) + 1;
+        for next in cfg.successors(block) {
+            match self.index[next] {
+                None => {
+                    self.visit(cfg, next);
+                    self.low_link[block] = self.low_link[block].min(self.low_link[next]);
+                }
+                Some(index) if self.on_stack[next] => {
+                    self.low_link[block] = self.low_link[block].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_link[block]) == self.index[block] {
+            let mut component = Vec::new();
+
+            while let Some(b) = self.stack.pop() {
+                self.on_stack[b] = false;
+                component.push(b);
 
-        if let Some(acc) = check_program_works(new_code.new_program()) {
-            return acc;
+                if b == block {
+                    break;
+                }
+            }
+
+            self.components.push(component);
         }
     }
 }
 
-fn check_program_works(program: Program) -> Option<isize> {
-    let mut already_visited = HashSet::new();
-    let mut prev_acc = 0;
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Equal,
+    NotEqual,
+    GreaterOrEqual,
+    Greater,
+}
 
-    for (index, acc) in program {
-        if !already_visited.insert(index) {
-            return None;
+impl Comparison {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            ">" => Some(Comparison::Greater),
+            _ => None,
         }
+    }
 
-        prev_acc = acc;
+    pub fn holds(&self, a: isize, b: isize) -> bool {
+        match self {
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b,
+            Comparison::GreaterOrEqual => a >= b,
+            Comparison::Greater => a > b,
+        }
     }
 
-    Some(prev_acc)
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Greater => ">",
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Instr {
-    NoOp,
-    Acc,
-    Jump,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Breakpoint {
+    At(usize),
+    Acc(Comparison, isize),
 }
 
-impl Instr {
-    pub fn parse(s: &str) -> Self {
-        match s {
-            "nop" => Instr::NoOp,
-            "acc" => Instr::Acc,
-            "jmp" => Instr::Jump,
-            _ => panic!("Unknown instruction: {}", s),
+impl Breakpoint {
+    pub fn parse(s: &str) -> Option<Self> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+
+        match words.as_slice() {
+            [index] => Some(Breakpoint::At(index.parse().ok()?)),
+            ["acc", comparison, value] => Some(Breakpoint::Acc(
+                Comparison::parse(comparison)?,
+                value.parse().ok()?,
+            )),
+            _ => None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-struct Code(Vec<(Instr, isize)>);
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::At(index) => write!(f, "instruction {}", index),
+            Breakpoint::Acc(comparison, value) => {
+                write!(f, "acc {} {}", comparison.symbol(), value)
+            }
+        }
+    }
+}
 
-impl Code {
-    pub fn parse(input: &str) -> Self {
-        let code = input
-            .lines()
-            .map(|l| {
-                let instr = Instr::parse(&l[..3]);
-                let x = (&l[4..]).parse().unwrap();
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Event {
+    Stepped,
+    Breakpoint(Breakpoint),
+    Watch { from: isize, to: isize },
+    Loop(usize),
+    StepLimit(usize),
+    OutOfBounds(isize),
+    Terminated,
+}
 
-                (instr, x)
-            })
-            .collect();
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Event::Stepped => write!(f, "stepped"),
+            Event::Breakpoint(breakpoint) => write!(f, "hit breakpoint on {}", breakpoint),
+            Event::Watch { from, to } => write!(f, "acc changed from {} to {}", from, to),
+            Event::Loop(index) => write!(f, "instruction {} is about to run a second time", index),
+            Event::StepLimit(steps) => write!(f, "still running after {} steps", steps),
+            Event::OutOfBounds(target) => write!(f, "jumped out of bounds to {}", target),
+            Event::Terminated => write!(f, "program terminated"),
+        }
+    }
+}
+
+const CONTINUE_LIMIT: usize = 100_000;
+
+struct Debugger<'a> {
+    program: Program<'a>,
+    history: Vec<Program<'a>>,
+    runs: Vec<usize>,
+    loop_reported: Option<usize>,
+    breakpoints: Vec<Breakpoint>,
+    watch_acc: bool,
+}
 
-        Code(code)
+impl<'a> Debugger<'a> {
+    pub fn new(code: &'a Code) -> Self {
+        Debugger {
+            program: code.new_program(),
+            history: Vec::new(),
+            runs: vec![0; code.0.len()],
+            loop_reported: None,
+            breakpoints: Vec::new(),
+            watch_acc: false,
+        }
     }
 
-    pub fn new_program(&self) -> Program {
-        Program {
-            code: self,
-            acc: 0,
-            pos: 0,
+    pub fn pos(&self) -> usize {
+        self.program.pos
+    }
+
+    pub fn acc(&self) -> isize {
+        self.program.acc()
+    }
+
+    pub fn is_terminated(&self) -> bool {
+        self.program.is_finished()
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
         }
     }
 
-    pub fn mutate_instruction(&mut self, start_from: usize) -> usize {
-        for (j, &(instr, x)) in (&self.0[start_from..]).iter().enumerate() {
-            let i = start_from + j;
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let len = self.breakpoints.len();
 
-            match instr {
-                Instr::Jump => {
-                    self.0[i] = (Instr::NoOp, x);
-                    return i;
-                }
-                Instr::NoOp => {
-                    self.0[i] = (Instr::Jump, x);
-                    return i;
+        self.breakpoints.retain(|&b| b != breakpoint);
+
+        self.breakpoints.len() != len
+    }
+
+    pub fn watch_acc(&mut self, watch: bool) {
+        self.watch_acc = watch;
+    }
+
+    pub fn step(&mut self) -> Event {
+        if self.is_terminated() {
+            return self.finished_event();
+        }
+
+        let previous = self.program.clone();
+
+        self.runs[previous.pos] += 1;
+        self.program.next();
+        self.history.push(previous);
+
+        self.event_after_step()
+    }
+
+    pub fn cont(&mut self) -> Event {
+        for _ in 0..CONTINUE_LIMIT {
+            let event = self.step();
+
+            if event != Event::Stepped {
+                return event;
+            }
+        }
+
+        Event::StepLimit(CONTINUE_LIMIT)
+    }
+
+    pub fn rewind(&mut self, steps: usize) -> usize {
+        let steps = steps.min(self.history.len());
+
+        for _ in 0..steps {
+            self.program = self.history.pop().unwrap();
+            self.runs[self.program.pos] -= 1;
+        }
+
+        if self.loop_reported > Some(self.history.len()) {
+            self.loop_reported = None;
+        }
+
+        steps
+    }
+
+    fn event_after_step(&mut self) -> Event {
+        let previous_acc = self.history.last().map_or(0, |p| p.acc());
+
+        if self.is_terminated() {
+            return self.finished_event();
+        }
+
+        for &breakpoint in &self.breakpoints {
+            let hit = match breakpoint {
+                Breakpoint::At(index) => index == self.pos(),
+                Breakpoint::Acc(comparison, value) => {
+                    comparison.holds(self.acc(), value) && !comparison.holds(previous_acc, value)
                 }
-                _ => {}
+            };
+
+            if hit {
+                return Event::Breakpoint(breakpoint);
             }
         }
 
-        panic!()
+        if self.watch_acc && previous_acc != self.acc() {
+            return Event::Watch {
+                from: previous_acc,
+                to: self.acc(),
+            };
+        }
+
+        if self.loop_reported.is_none() && self.runs[self.pos()] > 0 {
+            self.loop_reported = Some(self.history.len());
+
+            return Event::Loop(self.pos());
+        }
+
+        Event::Stepped
     }
-}
 
-#[derive(Debug, Clone)]
-struct Program<'a> {
-    code: &'a Code,
-    acc: isize,
-    pos: usize,
-}
+    fn finished_event(&self) -> Event {
+        match self.program.status {
+            Status::OutOfBounds(target) => Event::OutOfBounds(target),
+            _ => Event::Terminated,
+        }
+    }
 
-impl<'a> Iterator for Program<'a> {
-    type Item = (usize, isize);
+    pub fn listing(&self, context: usize) -> String {
+        let code = &self.program.code.0;
 
-    fn next(&mut self) -> Option<Self::Item> {
-        match self.code.0.get(self.pos)? {
-            (Instr::NoOp, _) => {
-                self.pos += 1;
+        let first = self.pos().saturating_sub(context);
+        let last = (self.pos() + context).min(code.len().saturating_sub(1));
+        let width = code.len().to_string().len();
+
+        let mut out = String::new();
+
+        for (i, instr) in code.iter().enumerate().take(last + 1).skip(first) {
+            let marker = if i == self.pos() { "=>" } else { "  " };
+            let breakpoint = if self.breakpoints.contains(&Breakpoint::At(i)) {
+                '*'
+            } else {
+                ' '
+            };
+
+            out += &format!(
+                "{}{}{:>width$}  {}\n",
+                marker,
+                breakpoint,
+                i,
+                instr,
+                width = width
+            );
+        }
+
+        if self.pos() >= code.len() {
+            out += &format!("=> {:>width$}  (end)\n", self.pos(), width = width);
+        }
+
+        out
+    }
+
+    pub fn command(&mut self, line: &str) -> String {
+        let (command, rest) = match line.find(' ') {
+            Some(i) => (&line[..i], line[i + 1..].trim()),
+            None => (line, ""),
+        };
+
+        let count = |rest: &str| rest.parse().unwrap_or(1);
+
+        match command {
+            "s" | "step" => {
+                let mut event = Event::Stepped;
+
+                for _ in 0..count(rest) {
+                    event = self.step();
+
+                    if event != Event::Stepped {
+                        break;
+                    }
+                }
+
+                self.report(event)
             }
-            (Instr::Acc, x) => {
-                self.acc += x;
-                self.pos += 1;
+            "c" | "continue" => {
+                let event = self.cont();
+
+                self.report(event)
             }
-            (Instr::Jump, x) => {
-                // Could overflow
-                let abs = x.abs() as usize;
+            "r" | "rewind" => {
+                let steps = self.rewind(count(rest));
 
-                if x.is_negative() {
-                    self.pos -= abs;
-                } else {
-                    self.pos += abs;
+                format!("rewound {} steps\n{}", steps, self.listing(0))
+            }
+            "b" | "break" => match Breakpoint::parse(rest) {
+                Some(breakpoint) => {
+                    self.add_breakpoint(breakpoint);
+
+                    format!("breakpoint on {}\n", breakpoint)
+                }
+                None => "usage: break INDEX | break acc OP VALUE\n".to_string(),
+            },
+            "d" | "delete" => match Breakpoint::parse(rest) {
+                Some(breakpoint) if self.remove_breakpoint(breakpoint) => {
+                    format!("deleted breakpoint on {}\n", breakpoint)
                 }
+                _ => format!("no breakpoint on {}\n", rest),
+            },
+            "watch" => {
+                self.watch_acc(true);
+
+                "watching acc\n".to_string()
             }
-        };
+            "unwatch" => {
+                self.watch_acc(false);
+
+                "stopped watching acc\n".to_string()
+            }
+            "p" | "print" => format!("{}acc = {}\n", self.listing(0), self.acc()),
+            "l" | "list" => self.listing(rest.parse().unwrap_or(5)),
+            "i" | "info" => {
+                let mut out = format!(
+                    "instruction {}, acc = {}, {} steps taken\n",
+                    self.pos(),
+                    self.acc(),
+                    self.history.len()
+                );
 
-        Some((self.pos, self.acc))
+                for breakpoint in &self.breakpoints {
+                    out += &format!("breakpoint on {}\n", breakpoint);
+                }
+
+                out
+            }
+            "" => String::new(),
+            _ => format!("unknown command: {}\n", command),
+        }
+    }
+
+    fn report(&self, event: Event) -> String {
+        format!("{}, acc = {}\n{}", event, self.acc(), self.listing(0))
     }
 }
 
@@ -175,13 +1745,407 @@ acc +6
     fn sample_input_part_one() {
         let code = Code::parse(SAMPLE_INPUT.trim());
 
-        assert_eq!(part_one(&code), 5);
+        assert_eq!(part_one(&code), Ok(5));
+    }
+
+    #[test]
+    fn part_one_needs_a_loop() {
+        let run = part_one(&Code::parse("acc +3\njmp -2")).unwrap_err();
+
+        assert_eq!(
+            run.outcome,
+            Outcome::OutOfBounds {
+                from: 1,
+                target: -1
+            }
+        );
+        assert_eq!(run.acc, 3);
+
+        let run = part_one(&Code::parse("acc +3\nhlt")).unwrap_err();
+
+        assert_eq!(run.outcome, Outcome::Terminated);
     }
 
     #[test]
     fn sample_input_part_two() {
         let code = Code::parse(SAMPLE_INPUT.trim());
 
-        assert_eq!(part_two(&code), 8);
+        assert_eq!(part_two(&code), Ok(8));
+    }
+
+    #[test]
+    fn debugger_steps_and_rewinds() {
+        let code = Code::parse(SAMPLE_INPUT.trim());
+        let mut debugger = Debugger::new(&code);
+
+        assert_eq!(debugger.step(), Event::Stepped);
+        assert_eq!(debugger.step(), Event::Stepped);
+        assert_eq!((debugger.pos(), debugger.acc()), (2, 1));
+
+        debugger.add_breakpoint(Breakpoint::At(4));
+        assert_eq!(debugger.cont(), Event::Breakpoint(Breakpoint::At(4)));
+        assert_eq!((debugger.pos(), debugger.acc()), (4, 5));
+
+        assert_eq!(debugger.cont(), Event::Loop(1));
+        assert_eq!(debugger.acc(), 5);
+
+        assert_eq!(debugger.rewind(3), 3);
+        assert_eq!((debugger.pos(), debugger.acc()), (7, 2));
+
+        debugger.remove_breakpoint(Breakpoint::At(4));
+        debugger.add_breakpoint(Breakpoint::Acc(Comparison::GreaterOrEqual, 3));
+        assert_eq!(
+            debugger.cont(),
+            Event::Breakpoint(Breakpoint::Acc(Comparison::GreaterOrEqual, 3))
+        );
+        assert_eq!((debugger.pos(), debugger.acc()), (4, 5));
+
+        debugger.watch_acc(true);
+        assert_eq!(debugger.rewind(100), 6);
+        assert_eq!(debugger.step(), Event::Stepped);
+        assert_eq!(debugger.step(), Event::Watch { from: 0, to: 1 });
+    }
+
+    #[test]
+    fn debugger_reports_a_loop_once() {
+        let code = Code::parse(SAMPLE_INPUT.trim());
+        let mut debugger = Debugger::new(&code);
+
+        assert_eq!(debugger.cont(), Event::Loop(1));
+
+        debugger.add_breakpoint(Breakpoint::At(6));
+        assert_eq!(debugger.cont(), Event::Breakpoint(Breakpoint::At(6)));
+        assert_eq!(debugger.step(), Event::Stepped);
+        assert_eq!(debugger.cont(), Event::Breakpoint(Breakpoint::At(6)));
+        assert_eq!(debugger.acc(), 11);
+
+        debugger.remove_breakpoint(Breakpoint::At(6));
+        assert_eq!(debugger.cont(), Event::StepLimit(CONTINUE_LIMIT));
+
+        debugger.rewind(CONTINUE_LIMIT + 10);
+        assert_eq!(debugger.cont(), Event::Loop(1));
+    }
+
+    #[test]
+    fn debugger_commands() {
+        let code = Code::parse(SAMPLE_INPUT.trim());
+        let mut debugger = Debugger::new(&code);
+
+        assert_eq!(debugger.command("break 7"), "breakpoint on instruction 7\n");
+        assert_eq!(
+            debugger.command("continue"),
+            "hit breakpoint on instruction 7, acc = 2\n=>*7  jmp -4\n"
+        );
+        assert_eq!(
+            debugger.command("list 1"),
+            "   6  acc +1\n=>*7  jmp -4\n   8  acc +6\n"
+        );
+        assert_eq!(
+            debugger.command("step 3"),
+            "instruction 1 is about to run a second time, acc = 5\n=> 1  acc +1\n"
+        );
+        assert_eq!(
+            debugger.command("rewind"),
+            "rewound 1 steps\n=> 4  jmp -3\n"
+        );
+        assert_eq!(debugger.command("print"), "=> 4  jmp -3\nacc = 5\n");
+        assert_eq!(
+            debugger.command("frobnicate"),
+            "unknown command: frobnicate\n"
+        );
+    }
+
+    #[test]
+    fn extended_instructions() {
+        let code = Code::parse(
+            "inp a
+set b +1
+jz a +4
+mul b a
+sub a +1
+jmp -3
+out b
+acc +2
+hlt
+acc +100",
+        );
+
+        let mut program = code.new_program();
+        program.machine.input = vec![5].into();
+
+        assert_eq!(program.by_ref().count(), 26);
+        assert_eq!(program.status, Status::Halted);
+        assert_eq!(program.pos, 8);
+        assert_eq!(program.acc(), 2);
+        assert_eq!(program.machine.output, vec![120]);
+        assert_eq!(code.0[3].to_string(), "mul b a");
+
+        let mut program = code.new_program();
+        assert_eq!(program.by_ref().count(), 1);
+        assert_eq!(program.machine.registers, [0; 5]);
+
+        let code = Code::parse(&format!("set a +{}\nacc a\nacc a", isize::MAX));
+        let run = code.new_program().run(10);
+
+        assert_eq!(run.outcome, Outcome::Terminated);
+        assert_eq!(run.acc, -2);
+    }
+
+    #[test]
+    fn custom_opcodes_and_parse_errors() {
+        let mut set = InstrSet::standard();
+
+        set.register("dbl", &[OperandKind::Register], Control::Next, |m, args| {
+            m.set(args[0], m.get(args[0]) * 2);
+            Flow::Next
+        });
+
+        let code = set.parse("acc +3\ndbl acc\ndbl acc").unwrap();
+        assert_eq!(code.new_program().last(), Some((3, 12)));
+
+        let error = |input: &str| set.parse(input).unwrap_err().to_string();
+
+        assert_eq!(
+            error("nop +0\nfoo +1"),
+            "line 2: unknown instruction \"foo\""
+        );
+        assert_eq!(error("jmp +1 +2"), "line 1: jmp takes 1 operands, found 2");
+        assert_eq!(error("set +1 +2"), "line 1: \"+1\" is not a valid operand");
+        assert_eq!(error("acc one"), "line 1: \"one\" is not a valid operand");
+    }
+
+    #[test]
+    fn repair_reports_every_fix() {
+        let code = Code::parse(SAMPLE_INPUT.trim());
+        let fixes = repairs(&code).unwrap();
+
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(
+            fixes[0].to_string(),
+            "instruction 7: jmp -4 -> nop -4, acc = 8"
+        );
+
+        let code = Code::parse("acc +1\nnop +3\njmp +0\nacc +10\nacc +100");
+        let fixes: Vec<(usize, isize)> = repairs(&code)
+            .unwrap()
+            .iter()
+            .map(|r| (r.index, r.acc))
+            .collect();
+
+        assert_eq!(fixes, vec![(1, 101), (2, 111)]);
+    }
+
+    #[test]
+    fn repair_with_huge_offsets() {
+        let code = Code::parse(&format!("nop +0\njmp +{}", isize::MAX));
+
+        assert_eq!(repairs(&code).unwrap()[0].index, 1);
+
+        let code = Code::parse(&format!(
+            "acc +{}\nacc +1\njmp +{}\nacc +1",
+            isize::MAX,
+            isize::MAX
+        ));
+
+        assert_eq!(repairs(&code).unwrap()[0].acc, isize::MIN + 1);
+    }
+
+    #[test]
+    fn repair_errors() {
+        let error = |input: &str| repairs(&Code::parse(input)).unwrap_err();
+
+        assert_eq!(error("nop +0\njmp -1\njmp -2"), RepairError::NoFix);
+        assert_eq!(error("acc +1\nacc +2"), RepairError::AlreadyTerminates(3));
+        assert_eq!(
+            error("jz a +1\njmp -1"),
+            RepairError::Unsupported(0, "jz a +1".to_string())
+        );
+    }
+
+    #[test]
+    fn run_outcomes() {
+        let run = |input: &str| Code::parse(input).new_program().run(1000);
+
+        assert_eq!(
+            run(SAMPLE_INPUT.trim()),
+            Run {
+                outcome: Outcome::InfiniteLoop(vec![1, 2, 3, 4, 6, 7].into_iter().collect()),
+                acc: 5,
+                steps: 7
+            }
+        );
+
+        assert_eq!(run("acc +1\njmp +1\nacc +2").outcome, Outcome::Terminated);
+        assert_eq!(run("acc +1\nhlt\nacc +2").acc, 1);
+
+        let below = run("acc +3\njmp -2");
+        assert_eq!(
+            below.outcome,
+            Outcome::OutOfBounds {
+                from: 1,
+                target: -1
+            }
+        );
+        assert_eq!(below.acc, 3);
+        assert_eq!(
+            below.outcome.to_string(),
+            "jumped out of bounds from instruction 1 to -1, before the start"
+        );
+
+        assert_eq!(
+            run("nop +0\njmp +2").outcome,
+            Outcome::OutOfBounds { from: 1, target: 3 }
+        );
+
+        let counting = run("add a +1\njmp -1");
+        assert_eq!(
+            counting.outcome,
+            Outcome::InfiniteLoop(vec![0, 1].into_iter().collect())
+        );
+
+        let counting = run("add a +1\njgt +1 -1");
+        assert_eq!(counting.outcome, Outcome::StepLimit);
+        assert_eq!(counting.steps, 1000);
+
+        let spinning = run("set a +1\nmul a -1\njnz a -1");
+        assert_eq!(
+            spinning.outcome,
+            Outcome::InfiniteLoop(vec![1, 2].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn control_flow_graph() {
+        let code = Code::parse(SAMPLE_INPUT.trim());
+        let cfg = Cfg::of(&code);
+
+        let blocks: Vec<(usize, usize)> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(blocks, vec![(0, 0), (1, 2), (3, 4), (5, 5), (6, 7), (8, 8)]);
+
+        assert_eq!(cfg.blocks[1].targets, vec![Target::Block(4)]);
+        assert_eq!(cfg.blocks[2].acc_delta, Some(3));
+        assert_eq!(cfg.unreachable(), vec![5, 8]);
+        assert_eq!(cfg.loops(), vec![&[1, 2, 4][..]]);
+
+        assert_eq!(cfg.predict(), Prediction::Loops(vec![1, 4, 2]));
+
+        match code.new_program().run(1000).outcome {
+            Outcome::InfiniteLoop(instructions) => {
+                assert_eq!(cfg.instructions(&[1, 4, 2]), instructions)
+            }
+            outcome => panic!("Unexpected outcome: {:?}", outcome),
+        }
+
+        let mut fixed = code.clone();
+        fixed.0[7].op = InstrSet::standard().opcode("nop").unwrap();
+        assert_eq!(Cfg::of(&fixed).predict(), Prediction::Terminates);
+
+        let dot = cfg.to_dot();
+        assert!(dot.contains("    b2 [label=\"3  acc +3\\l4  jmp -3\\l\", color=red];\n"));
+        assert!(dot.contains("    b5 -> end;\n"));
+    }
+
+    #[test]
+    fn control_flow_with_branches() {
+        let code = Code::parse("inp a\njz a +3\nadd acc +1\njmp -2\njmp +5\nset b a\njmp b");
+        let cfg = Cfg::of(&code);
+
+        assert_eq!(cfg.blocks[0].targets, vec![Target::Block(1), Target::End]);
+        assert_eq!(
+            cfg.blocks[1].targets,
+            vec![Target::Block(2), Target::Block(3)]
+        );
+        assert_eq!(cfg.blocks[2].acc_delta, None);
+        assert_eq!(cfg.blocks[3].targets, vec![Target::OutOfBounds(9)]);
+        assert_eq!(cfg.blocks[4].targets, vec![Target::Dynamic]);
+        assert_eq!(cfg.unreachable(), vec![5, 6]);
+        assert_eq!(cfg.predict(), Prediction::MayLoop(vec![vec![1, 2]]));
+
+        let code = Code::parse(&format!("acc +{}\nacc +{}", isize::MAX, isize::MAX));
+        assert_eq!(Cfg::of(&code).blocks[0].acc_delta, Some(-2));
+    }
+
+    #[test]
+    fn assemble_labels_and_comments() {
+        let source = "
+; count a down from three, adding it to acc
+        set a +3
+loop:   jz a done   ; leave once a reaches zero
+        add acc a
+        sub a +1
+        jmp loop
+done:";
+
+        let code = InstrSet::standard().assemble(source).unwrap();
+
+        assert_eq!(
+            code.to_string(),
+            "set a +3\njz a +4\nadd acc a\nsub a +1\njmp -3"
+        );
+        assert_eq!(code.new_program().run(100).acc, 6);
+
+        assert_eq!(
+            InstrSet::standard().assemble(SAMPLE_INPUT).unwrap(),
+            Code::parse(SAMPLE_INPUT.trim())
+        );
+    }
+
+    #[test]
+    fn assembler_errors() {
+        let set = InstrSet::standard();
+
+        assert_eq!(
+            set.assemble("nop +0\njmp nowhere").unwrap_err().to_string(),
+            "line 2: undefined label nowhere"
+        );
+        assert_eq!(
+            set.assemble("nop +0\na: jmp a").unwrap_err().to_string(),
+            "line 2: \"a\" is not a valid label"
+        );
+        assert_eq!(
+            set.assemble("x: nop +0\nx: jmp x").unwrap_err().to_string(),
+            "line 2: label x is already defined"
+        );
+    }
+
+    #[test]
+    fn disassemble_round_trip() {
+        let code = Code::parse(SAMPLE_INPUT.trim());
+
+        assert_eq!(
+            code.disassemble(),
+            "    nop +0
+L1:
+    acc +1
+    jmp L3
+L2:
+    acc +3
+    jmp L1
+    acc -99
+L3:
+    acc +1
+    jmp L2
+    acc +6
+"
+        );
+
+        assert_eq!(code.to_string(), SAMPLE_INPUT.trim());
+
+        let set = InstrSet::standard();
+
+        let huge_jumps = format!("nop +0\njmp +{}\njz a {}", isize::MAX, isize::MIN);
+
+        for text in &[
+            SAMPLE_INPUT.trim(),
+            "set a +3\njz a +4\nadd acc a\nsub a +1\njmp -3",
+            "inp b\njgt b +2\njmp -7\nout b\njmp b\nhlt",
+            &huge_jumps,
+        ] {
+            let code = set.parse(text).unwrap();
+
+            assert_eq!(set.assemble(&code.disassemble()).unwrap(), code);
+            assert_eq!(set.parse(&code.to_string()).unwrap(), code);
+            assert_eq!(code.to_string(), *text);
+        }
     }
 }
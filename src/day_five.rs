@@ -1,4 +1,7 @@
-use super::utils::start_day;
+use super::utils::{option, read_input_arg, start_day};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::RangeInclusive;
 
 pub fn main() {
     let input = start_day("five");
@@ -8,7 +11,81 @@ pub fn main() {
     println!();
 }
 
-fn part_one(input: &str) -> u16 {
+pub fn boarding_pass(args: &[String]) {
+    let geometry = read_geometry(args);
+
+    for arg in args.iter().filter(|a| !a.starts_with("--")) {
+        let numbers: Option<Vec<u32>> = arg.split(',').map(|x| x.parse().ok()).collect();
+
+        let no_such_seat = || {
+            format!(
+                "no such seat on this aircraft, seat IDs go up to {}",
+                geometry.max_seat_id()
+            )
+        };
+
+        let ticket = match (geometry.decode(arg), numbers.as_deref()) {
+            (Ok(ticket), _) => Ok(ticket),
+            (Err(_), Some(&[id])) => geometry.ticket_for_seat_id(id).ok_or_else(no_such_seat),
+            (Err(_), Some(&[row, col])) => geometry.ticket(row, col).ok_or_else(no_such_seat),
+            (Err(e), _) => Err(e.to_string()),
+        };
+
+        match ticket {
+            Ok(ticket) => {
+                let (row, col) = ticket.position();
+
+                println!(
+                    "{}: row {}, column {}, seat ID {}",
+                    geometry.encode(ticket),
+                    row,
+                    col,
+                    geometry.seat_id(ticket)
+                );
+            }
+            Err(e) => println!("{}: {}", arg, e),
+        }
+    }
+}
+
+pub fn missing_seat(args: &[String]) {
+    let geometry = read_geometry(args);
+    let input = read_input_arg(args, "five");
+
+    let map = SeatMap::of(geometry, &input);
+    let candidates = map.candidate_seats();
+
+    if candidates.is_empty() {
+        println!("No missing seat found");
+    }
+
+    for ticket in candidates {
+        println!("Missing seat: {}", map.describe(ticket));
+    }
+}
+
+pub fn seat_map(args: &[String]) {
+    let geometry = read_geometry(args);
+    let input = read_input_arg(args, "five");
+
+    let map = SeatMap::of(geometry, &input);
+
+    print!("{}", map.to_text());
+    println!();
+    print!("{}", map.report());
+}
+
+fn read_geometry(args: &[String]) -> Geometry {
+    match Geometry::from_args(args) {
+        Ok(geometry) => geometry,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn part_one(input: &str) -> u32 {
     input
         .lines()
         .map(|l| {
@@ -20,115 +97,546 @@ fn part_one(input: &str) -> u16 {
         .unwrap_or_default()
 }
 
-fn part_two(input: &str) -> usize {
-    let mut seats_taken = [false; 1024];
+fn part_two(input: &str) -> u32 {
+    let map = SeatMap::of(Geometry::default(), input);
+
+    let seat = *map.candidate_seats().first().expect("Failed to find seat");
+
+    seat.seat_id()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Geometry {
+    row_bits: u32,
+    col_bits: u32,
+    letters: [char; 4],
+    row_multiplier: u32,
+}
+
+impl Default for Geometry {
+    fn default() -> Self {
+        Geometry {
+            row_bits: 7,
+            col_bits: 3,
+            letters: ['F', 'B', 'L', 'R'],
+            row_multiplier: 8,
+        }
+    }
+}
+
+impl Geometry {
+    pub fn new(
+        row_bits: u32,
+        col_bits: u32,
+        letters: [char; 4],
+        row_multiplier: u32,
+    ) -> Result<Self, GeometryError> {
+        if row_bits.saturating_add(col_bits) > 31 {
+            return Err(GeometryError::TooManySeats { row_bits, col_bits });
+        }
+
+        for pair in letters.chunks(2) {
+            if pair[0] == pair[1] {
+                return Err(GeometryError::SameLetters(pair[0]));
+            }
+        }
+
+        let rows = 1 << row_bits;
+        let cols = 1 << col_bits;
+
+        if row_multiplier < cols {
+            return Err(GeometryError::MultiplierTooSmall {
+                multiplier: row_multiplier,
+                cols,
+            });
+        }
+
+        (rows - 1u32)
+            .checked_mul(row_multiplier)
+            .and_then(|id| id.checked_add(cols - 1))
+            .ok_or(GeometryError::SeatIdOverflow)?;
+
+        Ok(Geometry {
+            row_bits,
+            col_bits,
+            letters,
+            row_multiplier,
+        })
+    }
+
+    pub fn from_args(args: &[String]) -> Result<Self, GeometryError> {
+        let number = |name: &'static str, default: u32| match option(args, name) {
+            Some(x) => x
+                .parse()
+                .map_err(|_| GeometryError::InvalidOption(name, x.to_string())),
+            None => Ok(default),
+        };
+
+        let default = Geometry::default();
+
+        let row_bits = number("rows", default.row_bits)?;
+        let col_bits = number("cols", default.col_bits)?;
+
+        let letters = match option(args, "letters") {
+            Some(x) => {
+                let chars: Vec<char> = x.chars().collect();
+
+                match chars.as_slice() {
+                    &[a, b, c, d] => [a, b, c, d],
+                    _ => return Err(GeometryError::InvalidOption("letters", x.to_string())),
+                }
+            }
+            None => default.letters,
+        };
+
+        let row_multiplier = number("multiplier", 1u32.checked_shl(col_bits).unwrap_or(0))?;
+
+        Geometry::new(row_bits, col_bits, letters, row_multiplier)
+    }
+
+    pub fn rows(&self) -> u32 {
+        1 << self.row_bits
+    }
+
+    pub fn cols(&self) -> u32 {
+        1 << self.col_bits
+    }
+
+    pub fn pass_len(&self) -> usize {
+        (self.row_bits + self.col_bits) as usize
+    }
+
+    pub fn ticket(&self, row: u32, col: u32) -> Option<Ticket> {
+        if row < self.rows() && col < self.cols() {
+            Some(Ticket { row, col })
+        } else {
+            None
+        }
+    }
+
+    pub fn ticket_for_seat_id(&self, id: u32) -> Option<Ticket> {
+        let row = id.checked_div(self.row_multiplier)?;
+
+        self.ticket(row, id % self.row_multiplier)
+    }
+
+    pub fn seat_id(&self, ticket: Ticket) -> u32 {
+        ticket.row * self.row_multiplier + ticket.col
+    }
+
+    pub fn max_seat_id(&self) -> u32 {
+        self.seat_id(Ticket {
+            row: self.rows() - 1,
+            col: self.cols() - 1,
+        })
+    }
+
+    pub fn decode(&self, pass: &str) -> Result<Ticket, PassError> {
+        let found = pass.chars().count();
+
+        if found != self.pass_len() {
+            return Err(PassError::WrongLength {
+                expected: self.pass_len(),
+                found,
+            });
+        }
+
+        let mut row = 0;
+        let mut col = 0;
+
+        for (i, c) in pass.chars().enumerate() {
+            let (zero, one) = self.letters_at(i);
+
+            let bit = match c {
+                c if c == zero => 0,
+                c if c == one => 1,
+                c => {
+                    return Err(PassError::UnexpectedChar {
+                        position: i + 1,
+                        found: c,
+                        expected: (zero, one),
+                    })
+                }
+            };
+
+            if i < self.row_bits as usize {
+                row = (row << 1) | bit;
+            } else {
+                col = (col << 1) | bit;
+            }
+        }
 
-    let seat_ids = input.lines().map(|l| {
-        let ticket = Ticket::parse(l);
+        Ok(Ticket { row, col })
+    }
+
+    pub fn encode(&self, ticket: Ticket) -> String {
+        (0..self.pass_len())
+            .map(|i| {
+                let bit = if i < self.row_bits as usize {
+                    (ticket.row >> (self.row_bits as usize - 1 - i)) & 1
+                } else {
+                    (ticket.col >> (self.pass_len() - 1 - i)) & 1
+                };
 
-        ticket.seat_id() as usize
-    });
+                let (zero, one) = self.letters_at(i);
 
-    for id in seat_ids {
-        seats_taken[id] = true;
+                if bit == 0 {
+                    zero
+                } else {
+                    one
+                }
+            })
+            .collect()
     }
 
-    for id in 1..1023 {
-        if seats_taken[id - 1] && seats_taken[id + 1] && !seats_taken[id] {
-            return id;
+    fn letters_at(&self, i: usize) -> (char, char) {
+        if i < self.row_bits as usize {
+            (self.letters[0], self.letters[1])
+        } else {
+            (self.letters[2], self.letters[3])
         }
     }
+}
 
-    panic!("Failed to find seat")
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum GeometryError {
+    InvalidOption(&'static str, String),
+    TooManySeats { row_bits: u32, col_bits: u32 },
+    SameLetters(char),
+    MultiplierTooSmall { multiplier: u32, cols: u32 },
+    SeatIdOverflow,
 }
 
-#[derive(Debug, Copy, Clone)]
-enum RowInstr {
-    Front,
-    Back,
+impl fmt::Display for GeometryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GeometryError::InvalidOption(name, value) => {
+                write!(f, "invalid value {:?} for --{}", value, name)
+            }
+            GeometryError::TooManySeats { row_bits, col_bits } => write!(
+                f,
+                "{} row bits and {} column bits is more than 31 bits per pass",
+                row_bits, col_bits
+            ),
+            GeometryError::SameLetters(letter) => {
+                write!(f, "{:?} cannot stand for both 0 and 1", letter)
+            }
+            GeometryError::MultiplierTooSmall { multiplier, cols } => write!(
+                f,
+                "a row multiplier of {} gives different seats the same ID with {} columns",
+                multiplier, cols
+            ),
+            GeometryError::SeatIdOverflow => write!(f, "seat IDs do not fit in 32 bits"),
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum ColInstr {
-    Left,
-    Right,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PassError {
+    WrongLength {
+        expected: usize,
+        found: usize,
+    },
+    UnexpectedChar {
+        position: usize,
+        found: char,
+        expected: (char, char),
+    },
 }
 
-#[derive(Debug, Copy, Clone)]
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PassError::WrongLength { expected, found } => write!(
+                f,
+                "expected a pass of {} letters, found {}",
+                expected, found
+            ),
+            PassError::UnexpectedChar {
+                position,
+                found,
+                expected: (zero, one),
+            } => write!(
+                f,
+                "unexpected {:?} at position {}, expected {} or {}",
+                found, position, zero, one
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 struct Ticket {
-    row_steps: [RowInstr; 7],
-    col_steps: [ColInstr; 3],
+    row: u32,
+    col: u32,
 }
 
 impl Ticket {
     pub fn parse(text: &str) -> Self {
-        let mut chars = text.chars();
+        Geometry::default().decode(text).unwrap()
+    }
 
-        let mut row_steps = [RowInstr::Front; 7];
-        let mut col_steps = [ColInstr::Left; 3];
+    fn position(&self) -> (u32, u32) {
+        (self.row, self.col)
+    }
 
-        for instr in row_steps.iter_mut() {
-            *instr = match chars.next().unwrap() {
-                'F' => RowInstr::Front,
-                'B' => RowInstr::Back,
-                c => panic!("Unexpected char: {}", c),
-            };
+    pub fn seat_id(&self) -> u32 {
+        Geometry::default().seat_id(*self)
+    }
+}
+
+struct SeatMap<'a> {
+    geometry: Geometry,
+    passes: BTreeMap<Ticket, Vec<usize>>,
+    invalid: Vec<InvalidPass<'a>>,
+}
+
+struct InvalidPass<'a> {
+    line: usize,
+    text: &'a str,
+    error: PassError,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Gap {
+    first: Ticket,
+    last: Ticket,
+    seats: usize,
+}
+
+impl<'a> SeatMap<'a> {
+    pub fn of(geometry: Geometry, input: &'a str) -> Self {
+        let mut passes: BTreeMap<Ticket, Vec<usize>> = BTreeMap::new();
+        let mut invalid = Vec::new();
+
+        for (i, text) in input.lines().enumerate() {
+            let text = text.trim();
+
+            if text.is_empty() {
+                continue;
+            }
+
+            match geometry.decode(text) {
+                Ok(ticket) => passes.entry(ticket).or_default().push(i + 1),
+                Err(error) => invalid.push(InvalidPass {
+                    line: i + 1,
+                    text,
+                    error,
+                }),
+            }
         }
 
-        for instr in col_steps.iter_mut() {
-            *instr = match chars.next().unwrap() {
-                'L' => ColInstr::Left,
-                'R' => ColInstr::Right,
-                c => panic!("Unexpected char: {}", c),
-            };
+        SeatMap {
+            geometry,
+            passes,
+            invalid,
         }
+    }
+
+    fn index(&self, ticket: Ticket) -> u64 {
+        u64::from(ticket.row) * u64::from(self.geometry.cols()) + u64::from(ticket.col)
+    }
+
+    fn ticket_at(&self, index: u64) -> Ticket {
+        let cols = u64::from(self.geometry.cols());
 
         Ticket {
-            row_steps,
-            col_steps,
+            row: (index / cols) as u32,
+            col: (index % cols) as u32,
         }
     }
 
-    fn position(&self) -> (u16, u16) {
-        let mut row_hi = 127;
-        let mut row_lo = 0;
+    fn lines_for(&self, ticket: Ticket) -> &[usize] {
+        self.passes.get(&ticket).map_or(&[], |lines| lines)
+    }
 
-        for step in &self.row_steps {
-            let rows_change = ((row_hi - row_lo) + 1) / 2;
+    pub fn is_occupied(&self, ticket: Ticket) -> bool {
+        self.passes.contains_key(&ticket)
+    }
 
-            match step {
-                RowInstr::Front => {
-                    row_hi -= rows_change;
-                }
-                RowInstr::Back => {
-                    row_lo += rows_change;
-                }
+    fn is_occupied_id(&self, id: Option<u32>) -> bool {
+        id.and_then(|id| self.geometry.ticket_for_seat_id(id))
+            .is_some_and(|ticket| self.is_occupied(ticket))
+    }
+
+    pub fn num_occupied(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn occupied_rows(&self) -> Option<RangeInclusive<u32>> {
+        let first = self.passes.keys().next()?;
+        let last = self.passes.keys().next_back()?;
+
+        Some(first.row..=last.row)
+    }
+
+    pub fn missing_rows(&self) -> (Option<RangeInclusive<u32>>, Option<RangeInclusive<u32>>) {
+        let last_row = self.geometry.rows() - 1;
+
+        match self.occupied_rows() {
+            Some(rows) => {
+                let front = if *rows.start() > 0 {
+                    Some(0..=rows.start() - 1)
+                } else {
+                    None
+                };
+
+                let back = if *rows.end() < last_row {
+                    Some(rows.end() + 1..=last_row)
+                } else {
+                    None
+                };
+
+                (front, back)
             }
+            None => (Some(0..=last_row), None),
         }
+    }
+
+    pub fn gaps(&self) -> Vec<Gap> {
+        let occupied: Vec<u64> = self.passes.keys().map(|&t| self.index(t)).collect();
 
-        let mut col_hi = 7;
-        let mut col_lo = 0;
+        occupied
+            .windows(2)
+            .filter(|pair| pair[1] - pair[0] > 1)
+            .map(|pair| Gap {
+                first: self.ticket_at(pair[0] + 1),
+                last: self.ticket_at(pair[1] - 1),
+                seats: (pair[1] - pair[0] - 1) as usize,
+            })
+            .collect()
+    }
 
-        for step in &self.col_steps {
-            let cols_change = ((col_hi - col_lo) + 1) / 2;
+    pub fn candidate_seats(&self) -> Vec<Ticket> {
+        let mut candidates: Vec<Ticket> = self
+            .passes
+            .keys()
+            .filter_map(|&ticket| {
+                let id = self.geometry.seat_id(ticket).checked_add(1)?;
+                let seat = self.geometry.ticket_for_seat_id(id)?;
 
-            match step {
-                ColInstr::Left => {
-                    col_hi -= cols_change;
-                }
-                ColInstr::Right => {
-                    col_lo += cols_change;
+                if !self.is_occupied(seat) && self.is_occupied_id(id.checked_add(1)) {
+                    Some(seat)
+                } else {
+                    None
                 }
+            })
+            .collect();
+
+        candidates.sort_unstable();
+
+        candidates
+    }
+
+    pub fn duplicates(&self) -> Vec<(Ticket, &[usize])> {
+        self.passes
+            .iter()
+            .filter(|(_, lines)| lines.len() > 1)
+            .map(|(&ticket, lines)| (ticket, lines.as_slice()))
+            .collect()
+    }
+
+    pub fn describe(&self, ticket: Ticket) -> String {
+        format!(
+            "{} (row {}, column {})",
+            self.geometry.seat_id(ticket),
+            ticket.row,
+            ticket.col
+        )
+    }
+
+    pub fn to_text(&self) -> String {
+        let occupied_rows = self.occupied_rows();
+        let width = (self.geometry.rows() - 1).to_string().len();
+
+        let mut out = String::new();
+
+        for row in 0..self.geometry.rows() {
+            let missing = !occupied_rows
+                .as_ref()
+                .is_some_and(|rows| rows.contains(&row));
+
+            out += &format!("{:>width$} ", row, width = width);
+
+            for col in 0..self.geometry.cols() {
+                let lines = self.lines_for(Ticket { row, col });
+
+                out.push(match lines.len() {
+                    _ if missing => '-',
+                    0 => '.',
+                    1 => '#',
+                    _ => '!',
+                });
             }
+
+            out.push('\n');
         }
 
-        (row_hi, col_hi)
+        out
     }
 
-    pub fn seat_id(&self) -> u16 {
-        let (row, col) = self.position();
+    pub fn report(&self) -> String {
+        let mut out = format!(
+            "Occupied: {} of {} seats\n",
+            self.num_occupied(),
+            u64::from(self.geometry.rows()) * u64::from(self.geometry.cols())
+        );
+
+        let (front, back) = self.missing_rows();
+
+        for (rows, end) in [(front, "front"), (back, "back")].iter() {
+            if let Some(rows) = rows {
+                out += &format!(
+                    "Missing rows at the {}: {}-{}\n",
+                    end,
+                    rows.start(),
+                    rows.end()
+                );
+            }
+        }
+
+        out += &format!("Gaps: {}\n", self.gaps().len());
+
+        for gap in self.gaps() {
+            if gap.seats == 1 {
+                out += &format!("  seat {}\n", self.describe(gap.first));
+            } else {
+                out += &format!(
+                    "  {} seats from {} to {}\n",
+                    gap.seats,
+                    self.describe(gap.first),
+                    self.describe(gap.last)
+                );
+            }
+        }
+
+        let candidates: Vec<String> = self
+            .candidate_seats()
+            .iter()
+            .map(|&ticket| self.geometry.seat_id(ticket).to_string())
+            .collect();
+
+        out += &format!("Candidate seats: {}\n", candidates.join(", "));
+
+        out += &format!("Duplicate passes: {}\n", self.duplicates().len());
 
-        (row * 8) + col
+        for (ticket, lines) in self.duplicates() {
+            let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+
+            out += &format!(
+                "  seat {} on lines {}\n",
+                self.describe(ticket),
+                lines.join(", ")
+            );
+        }
+
+        out += &format!("Invalid passes: {}\n", self.invalid.len());
+
+        for pass in &self.invalid {
+            out += &format!("  line {}: {:?}: {}\n", pass.line, pass.text, pass.error);
+        }
+
+        out
     }
 }
 
@@ -149,4 +657,193 @@ mod tests {
         assert_eq!(Ticket::parse("FFFBBBFRRR").seat_id(), 119);
         assert_eq!(Ticket::parse("BBFFBBFRLL").seat_id(), 820);
     }
+
+    #[test]
+    fn encode_seat() {
+        let geometry = Geometry::default();
+
+        let ticket = geometry.ticket_for_seat_id(567).unwrap();
+        assert_eq!(geometry.encode(ticket), "BFFFBBFRRR");
+
+        let ticket = geometry.ticket(102, 4).unwrap();
+        assert_eq!(geometry.encode(ticket), "BBFFBBFRLL");
+    }
+
+    #[test]
+    fn every_seat_round_trips() {
+        let geometry = Geometry::default();
+
+        for id in 0..1024 {
+            let ticket = geometry.ticket_for_seat_id(id).unwrap();
+            let (row, col) = ticket.position();
+
+            assert_eq!(geometry.seat_id(ticket), id);
+            assert_eq!(geometry.ticket(row, col), Some(ticket));
+            assert_eq!(geometry.decode(&geometry.encode(ticket)), Ok(ticket));
+        }
+
+        assert_eq!(geometry.ticket_for_seat_id(1024), None);
+    }
+
+    #[test]
+    fn custom_geometry() {
+        let geometry = Geometry::new(5, 2, ['0', '1', 'a', 'b'], 10).unwrap();
+
+        let ticket = geometry.decode("10011ba").unwrap();
+
+        assert_eq!(ticket.position(), (19, 2));
+        assert_eq!(geometry.seat_id(ticket), 192);
+        assert_eq!(geometry.max_seat_id(), 313);
+
+        for id in (0..=geometry.max_seat_id()).filter(|id| id % 10 < 4) {
+            let ticket = geometry.ticket_for_seat_id(id).unwrap();
+
+            assert_eq!(geometry.decode(&geometry.encode(ticket)), Ok(ticket));
+        }
+
+        assert_eq!(geometry.ticket_for_seat_id(195), None);
+    }
+
+    #[test]
+    fn invalid_passes_rejected() {
+        let geometry = Geometry::default();
+
+        assert_eq!(
+            geometry.decode("BFFFBBFRR"),
+            Err(PassError::WrongLength {
+                expected: 10,
+                found: 9
+            })
+        );
+        assert_eq!(
+            geometry.decode("BFFFBBRRRR"),
+            Err(PassError::UnexpectedChar {
+                position: 7,
+                found: 'R',
+                expected: ('F', 'B')
+            })
+        );
+    }
+
+    #[test]
+    fn missing_seat_any_size() {
+        let geometry = Geometry::new(2, 1, ['F', 'B', 'L', 'R'], 2).unwrap();
+
+        let passes = |ids: &[u32]| {
+            ids.iter()
+                .map(|&id| geometry.encode(geometry.ticket_for_seat_id(id).unwrap()) + "\n")
+                .collect::<String>()
+        };
+
+        let input = passes(&[1, 2, 4, 5]);
+        let map = SeatMap::of(geometry.clone(), &input);
+        assert_eq!(map.candidate_seats(), vec![Ticket { row: 1, col: 1 }]);
+
+        let input = passes(&[0, 1, 2, 3]);
+        assert_eq!(
+            SeatMap::of(geometry.clone(), &input).candidate_seats(),
+            vec![]
+        );
+
+        let input = passes(&[0, 2, 4, 6]);
+        assert_eq!(SeatMap::of(geometry, &input).candidate_seats().len(), 3);
+    }
+
+    #[test]
+    fn seat_map_report() {
+        let geometry = Geometry::new(3, 2, ['F', 'B', 'L', 'R'], 4).unwrap();
+
+        let input = "FFBLL\nFFBLR\nFFBRR\r\n\nFBBRR\nBFFLL\nBFFLR\nFFBLR\nFFBL\nFFBLX\n";
+        let map = SeatMap::of(geometry, input);
+
+        assert_eq!(
+            map.to_text(),
+            "0 ----\n1 #!.#\n2 ....\n3 ...#\n4 ##..\n5 ----\n6 ----\n7 ----\n"
+        );
+        assert_eq!(map.missing_rows(), (Some(0..=0), Some(5..=7)));
+        assert_eq!(
+            map.gaps(),
+            vec![
+                Gap {
+                    first: Ticket { row: 1, col: 2 },
+                    last: Ticket { row: 1, col: 2 },
+                    seats: 1
+                },
+                Gap {
+                    first: Ticket { row: 2, col: 0 },
+                    last: Ticket { row: 3, col: 2 },
+                    seats: 7
+                },
+            ]
+        );
+        assert_eq!(map.candidate_seats(), vec![Ticket { row: 1, col: 2 }]);
+        assert_eq!(
+            map.duplicates(),
+            vec![(Ticket { row: 1, col: 1 }, &[2, 8][..])]
+        );
+        assert_eq!(map.invalid.len(), 2);
+        assert_eq!(map.num_occupied(), 6);
+    }
+
+    #[test]
+    fn sparse_seat_map() {
+        let geometry = Geometry::new(20, 10, ['F', 'B', 'L', 'R'], 1024).unwrap();
+
+        let input: String = [5, 7, 1 << 29]
+            .iter()
+            .map(|&id| geometry.encode(geometry.ticket_for_seat_id(id).unwrap()) + "\n")
+            .collect();
+
+        let map = SeatMap::of(geometry, &input);
+
+        assert_eq!(map.candidate_seats(), vec![Ticket { row: 0, col: 6 }]);
+        assert_eq!(
+            map.gaps().iter().map(|gap| gap.seats).collect::<Vec<_>>(),
+            vec![1, (1 << 29) - 8]
+        );
+        assert_eq!(map.occupied_rows(), Some(0..=1 << 19));
+    }
+
+    #[test]
+    fn invalid_geometry_rejected() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+
+        assert_eq!(
+            Geometry::from_args(&args(&["--rows=32"])),
+            Err(GeometryError::TooManySeats {
+                row_bits: 32,
+                col_bits: 3
+            })
+        );
+        assert_eq!(
+            Geometry::from_args(&args(&["--rows=20", "--cols=12"])),
+            Err(GeometryError::TooManySeats {
+                row_bits: 20,
+                col_bits: 12
+            })
+        );
+        assert_eq!(
+            Geometry::from_args(&args(&["--letters=FBLL"])),
+            Err(GeometryError::SameLetters('L'))
+        );
+        assert_eq!(
+            Geometry::from_args(&args(&["--letters=FBL"])),
+            Err(GeometryError::InvalidOption("letters", "FBL".to_string()))
+        );
+        assert_eq!(
+            Geometry::from_args(&args(&["--multiplier=7"])),
+            Err(GeometryError::MultiplierTooSmall {
+                multiplier: 7,
+                cols: 8
+            })
+        );
+        assert_eq!(
+            Geometry::from_args(&args(&["--rows=16", "--multiplier=1048576"])),
+            Err(GeometryError::SeatIdOverflow)
+        );
+        assert_eq!(
+            Geometry::from_args(&args(&["--rows=8", "--cols=2", "--letters=0101"])),
+            Geometry::new(8, 2, ['0', '1', '0', '1'], 4)
+        );
+    }
 }
@@ -1,5 +1,14 @@
-use super::utils::start_day;
-use std::collections::HashMap;
+use super::csv;
+use super::json::{self, Value};
+use super::records::{blank_line_groups, Record};
+use super::utils::{option, path_arg, read_input_arg, start_day};
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::RangeInclusive;
+
+const DEFAULT_SCHEMA: &str = include_str!("schemas/passport.txt");
 
 pub fn main() {
     let input = start_day("four");
@@ -11,8 +20,81 @@ pub fn main() {
     println!();
 }
 
+pub fn check(args: &[String]) {
+    let schema = match option(args, "schema") {
+        Some(path) => Schema::parse(&std::fs::read_to_string(path).unwrap()),
+        None => Schema::default(),
+    };
+
+    let input = read_input_arg(args, "four");
+    let passports = parse_passports(&input);
+
+    print!("{}", BatchReport::of(&passports, &schema).to_text());
+}
+
+pub fn export(args: &[String]) {
+    let input = read_input_arg(args, "four");
+    let passports = parse_passports(&input);
+
+    let schema = Schema::default();
+    let with_validity = args.iter().any(|a| a == "--valid");
+
+    if args.iter().any(|a| a == "--csv") {
+        print!("{}", export_csv(&passports, &schema, with_validity));
+    } else {
+        print!("{}", export_json(&passports, &schema, with_validity));
+    }
+}
+
+pub fn import(args: &[String]) {
+    let input = match path_arg(args) {
+        Some(path) => std::fs::read_to_string(path).unwrap(),
+        None => {
+            eprintln!("passport-import needs a JSON or CSV file to read");
+            std::process::exit(1);
+        }
+    };
+
+    let batch = if args.iter().any(|a| a == "--csv") {
+        import_csv(&input)
+    } else {
+        import_json(&input)
+    };
+
+    match batch {
+        Ok(batch) => print!("{}", batch),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn normalise(args: &[String]) {
+    let schema = match option(args, "schema") {
+        Some(path) => Schema::parse(&std::fs::read_to_string(path).unwrap()),
+        None => Schema::default(),
+    };
+
+    let fixes: Vec<Fix> = match option(args, "fix") {
+        Some(names) => names.split(',').map(Fix::parse).collect(),
+        None => Fix::ALL.to_vec(),
+    };
+
+    let input = read_input_arg(args, "four");
+    let passports = parse_passports(&input);
+
+    print!(
+        "{}",
+        NormalisationReport::of(&passports, &schema, &fixes).to_text()
+    );
+}
+
 fn parse_passports(input: &str) -> Vec<Passport> {
-    input.split("\n\n").map(Passport::parse).collect()
+    blank_line_groups(input)
+        .iter()
+        .map(Passport::parse)
+        .collect()
 }
 
 fn part_one(passports: &[Passport]) -> usize {
@@ -23,24 +105,70 @@ fn part_one(passports: &[Passport]) -> usize {
 }
 
 fn part_two(passports: &[Passport]) -> usize {
+    let schema = Schema::default();
+
     passports
         .iter()
-        .filter(|passport| passport.is_valid_v2())
+        .filter(|passport| passport.is_valid_for(&schema))
         .count()
 }
 
+#[derive(Debug, Clone)]
 struct Passport<'a> {
-    fields: HashMap<&'a str, &'a str>,
+    fields: HashMap<&'a str, Field<'a>>,
+    lines: RangeInclusive<usize>,
+    issues: Vec<ParseIssue<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field<'a> {
+    value: Cow<'a, str>,
+    line: usize,
+    column: usize,
 }
 
 impl<'a> Passport<'a> {
-    pub fn parse(text: &'a str) -> Self {
-        let mut fields = HashMap::with_capacity(8);
+    pub fn parse(record: &Record<'a>) -> Self {
+        let mut fields: HashMap<&str, Field> = HashMap::with_capacity(8);
+        let mut issues = Vec::new();
+
+        for token in record.tokens() {
+            let (key, value) = match split_key_value(token.text) {
+                Some(pair) => pair,
+                None => {
+                    issues.push(ParseIssue::Malformed {
+                        token: token.text,
+                        line: token.line,
+                        column: token.column,
+                    });
+
+                    continue;
+                }
+            };
+
+            let field = Field {
+                value: Cow::Borrowed(value),
+                line: token.line,
+                column: token.column,
+            };
 
-        let kv_pairs = text.split_ascii_whitespace().map(split_key_value);
-        fields.extend(kv_pairs);
+            match fields.get(key) {
+                Some(first) => issues.push(ParseIssue::Duplicate {
+                    key,
+                    first: first.clone(),
+                    field,
+                }),
+                None => {
+                    fields.insert(key, field);
+                }
+            }
+        }
 
-        Passport { fields }
+        Passport {
+            fields,
+            lines: record.first_line()..=record.last_line(),
+            issues,
+        }
     }
 
     pub fn is_valid(&self) -> bool {
@@ -49,73 +177,837 @@ impl<'a> Passport<'a> {
         required_fields.iter().all(|k| self.fields.contains_key(k))
     }
 
-    pub fn is_valid_v2(&self) -> bool {
-        self.valid_year_field("byr", 1920, 2002)
-            && self.valid_year_field("iyr", 2010, 2020)
-            && self.valid_year_field("eyr", 2020, 2030)
-            && self.height_valid()
-            && self.hcl_valid()
-            && self.ecl_valid()
-            && self.pid_valid()
+    pub fn is_valid_for(&self, schema: &Schema) -> bool {
+        schema
+            .fields
+            .iter()
+            .all(|rule| match self.fields.get(rule.name.as_str()) {
+                Some(field) => rule.kind.accepts(&field.value),
+                None => !rule.required,
+            })
+    }
+
+    pub fn problems(&self, schema: &Schema) -> Vec<Problem> {
+        let mut problems = Vec::new();
+
+        for rule in &schema.fields {
+            match self.fields.get(rule.name.as_str()) {
+                Some(field) => {
+                    if let Some(reason) = rule.kind.problem(&field.value) {
+                        problems.push(Problem::Invalid {
+                            field: rule.name.clone(),
+                            value: field.value.to_string(),
+                            reason,
+                        });
+                    }
+                }
+                None if rule.required => problems.push(Problem::Missing(rule.name.clone())),
+                None => {}
+            }
+        }
+
+        let mut unknown: Vec<_> = self
+            .fields
+            .keys()
+            .filter(|&&k| !schema.fields.iter().any(|rule| rule.name == k))
+            .map(|k| Problem::Unknown(k.to_string()))
+            .collect();
+
+        unknown.sort_by_key(|problem| problem.to_string());
+        problems.extend(unknown);
+
+        problems
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ParseIssue<'a> {
+    Malformed {
+        token: &'a str,
+        line: usize,
+        column: usize,
+    },
+    Duplicate {
+        key: &'a str,
+        first: Field<'a>,
+        field: Field<'a>,
+    },
+}
+
+impl<'a> ParseIssue<'a> {
+    pub fn summary_key(&self) -> String {
+        match self {
+            ParseIssue::Malformed { .. } => "malformed token".to_string(),
+            ParseIssue::Duplicate { key, .. } => format!("duplicate {}", key),
+        }
+    }
+}
+
+impl<'a> fmt::Display for ParseIssue<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseIssue::Malformed {
+                token,
+                line,
+                column,
+            } => write!(
+                f,
+                "line {}, column {}: {:?} is not a key:value pair",
+                line, column, token
+            ),
+            ParseIssue::Duplicate { key, first, field } => write!(
+                f,
+                "line {}, column {}: duplicate {} {:?} ignored, keeping {:?} from line {}, column {}",
+                field.line, field.column, key, field.value, first.value, first.line, first.column
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Problem {
+    Missing(String),
+    Invalid {
+        field: String,
+        value: String,
+        reason: String,
+    },
+    Unknown(String),
+}
+
+impl Problem {
+    pub fn field(&self) -> &str {
+        match self {
+            Problem::Missing(field) | Problem::Unknown(field) => field,
+            Problem::Invalid { field, .. } => field,
+        }
+    }
+
+    pub fn summary_key(&self) -> String {
+        match self {
+            Problem::Missing(field) => format!("missing {}", field),
+            Problem::Invalid { field, .. } => format!("invalid {}", field),
+            Problem::Unknown(field) => format!("unknown {}", field),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::Missing(field) => write!(f, "{}: missing", field),
+            Problem::Invalid {
+                field,
+                value,
+                reason,
+            } => write!(f, "{}: {:?} {}", field, value, reason),
+            Problem::Unknown(field) => write!(f, "{}: unknown field", field),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PassportReport<'a> {
+    lines: RangeInclusive<usize>,
+    issues: Vec<ParseIssue<'a>>,
+    problems: Vec<(Problem, Option<(usize, usize)>)>,
+}
+
+#[derive(Debug)]
+struct BatchReport<'a> {
+    passports: Vec<PassportReport<'a>>,
+}
+
+impl<'a> BatchReport<'a> {
+    pub fn of(passports: &[Passport<'a>], schema: &Schema) -> Self {
+        let passports = passports
+            .iter()
+            .map(|passport| PassportReport {
+                lines: passport.lines.clone(),
+                issues: passport.issues.clone(),
+                problems: passport
+                    .problems(schema)
+                    .into_iter()
+                    .map(|problem| {
+                        let position = passport
+                            .fields
+                            .get(problem.field())
+                            .map(|field| (field.line, field.column));
+
+                        (problem, position)
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        BatchReport { passports }
+    }
+
+    pub fn num_valid(&self) -> usize {
+        self.passports
+            .iter()
+            .filter(|report| {
+                report
+                    .problems
+                    .iter()
+                    .all(|(problem, _)| matches!(problem, Problem::Unknown(_)))
+            })
+            .count()
+    }
+
+    pub fn summary(&self) -> BTreeMap<String, usize> {
+        let mut summary = BTreeMap::new();
+
+        for report in &self.passports {
+            let keys = report.issues.iter().map(ParseIssue::summary_key).chain(
+                report
+                    .problems
+                    .iter()
+                    .map(|(problem, _)| problem.summary_key()),
+            );
+
+            for key in keys {
+                *summary.entry(key).or_insert(0) += 1;
+            }
+        }
+
+        summary
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        for report in &self.passports {
+            if report.issues.is_empty() && report.problems.is_empty() {
+                continue;
+            }
+
+            out += &format!(
+                "Passport on lines {}-{}:\n",
+                report.lines.start(),
+                report.lines.end()
+            );
+
+            for issue in &report.issues {
+                out += &format!("  {}\n", issue);
+            }
+
+            for (problem, position) in &report.problems {
+                match position {
+                    Some((line, column)) => {
+                        out += &format!("  line {}, column {}: {}\n", line, column, problem)
+                    }
+                    None => out += &format!("  {}\n", problem),
+                }
+            }
+        }
+
+        out += &format!(
+            "\nValid passports: {} of {}\n",
+            self.num_valid(),
+            self.passports.len()
+        );
+
+        for (problem, count) in self.summary() {
+            out += &format!("  {}: {}\n", problem, count);
+        }
+
+        out
+    }
+}
+
+#[derive(Debug)]
+struct Schema {
+    fields: Vec<FieldRule>,
+}
+
+impl Schema {
+    pub fn parse(text: &str) -> Self {
+        let fields = text
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(FieldRule::parse)
+            .collect();
+
+        Schema { fields }
+    }
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Schema::parse(DEFAULT_SCHEMA)
+    }
+}
+
+#[derive(Debug)]
+struct FieldRule {
+    name: String,
+    required: bool,
+    kind: FieldKind,
+}
+
+impl FieldRule {
+    pub fn parse(line: &str) -> Self {
+        let mut words = line.split_ascii_whitespace();
+
+        let name = words.next().unwrap().to_string();
+
+        let required = match words.next() {
+            Some("required") => true,
+            Some("optional") => false,
+            other => panic!(
+                "Expected required or optional for {}, got {:?}",
+                name, other
+            ),
+        };
+
+        let kind = match words.next() {
+            None => FieldKind::Any,
+            Some("int") => {
+                let (min, max) = parse_range(words.next().unwrap());
+
+                FieldKind::IntRange(min, max)
+            }
+            Some("unit") => FieldKind::UnitRange(
+                words
+                    .map(|range| {
+                        let unit_start = range.rfind(|c: char| c.is_ascii_digit()).unwrap() + 1;
+                        let (min, max) = parse_range(&range[..unit_start]);
+
+                        (range[unit_start..].to_string(), min, max)
+                    })
+                    .collect(),
+            ),
+            Some("hex-colour") => FieldKind::HexColour,
+            Some("enum") => FieldKind::Enum(words.map(str::to_string).collect()),
+            Some("digits") => FieldKind::Digits(words.next().unwrap().parse().unwrap()),
+            Some("regex") => {
+                let pattern = words.collect::<Vec<_>>().join(" ");
+
+                FieldKind::Regex(Regex::new(&format!("^(?:{})$", pattern)).unwrap())
+            }
+            Some(other) => panic!("Unknown field type: {}", other),
+        };
+
+        FieldRule {
+            name,
+            required,
+            kind,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum FieldKind {
+    Any,
+    IntRange(u64, u64),
+    UnitRange(Vec<(String, u64, u64)>),
+    HexColour,
+    Enum(Vec<String>),
+    Digits(usize),
+    Regex(Regex),
+}
+
+impl FieldKind {
+    pub fn accepts(&self, value: &str) -> bool {
+        self.problem(value).is_none()
     }
 
-    fn valid_year_field(&self, name: &str, min: u32, max: u32) -> bool {
-        self.fields
-            .get(name)
-            .and_then(|s| s.parse::<u32>().ok())
-            .map(|y| y >= min && y <= max)
-            .unwrap_or(false)
+    pub fn problem(&self, value: &str) -> Option<String> {
+        match self {
+            FieldKind::Any => None,
+            FieldKind::IntRange(min, max) => range_problem(value, *min, *max, ""),
+            FieldKind::UnitRange(units) => {
+                let mut matching: Vec<&(String, u64, u64)> = units
+                    .iter()
+                    .filter(|(unit, _, _)| value.ends_with(unit.as_str()))
+                    .collect();
+
+                if matching.is_empty() {
+                    let units: Vec<&str> = units.iter().map(|(unit, _, _)| &unit[..]).collect();
+
+                    return Some(format!("has no unit, expected one of {}", units.join(", ")));
+                }
+
+                matching.sort_by_key(|(unit, _, _)| std::cmp::Reverse(unit.len()));
+
+                let mut problem = None;
+
+                for (unit, min, max) in matching {
+                    match range_problem(&value[..value.len() - unit.len()], *min, *max, unit) {
+                        None => return None,
+                        Some(p) => problem = problem.or(Some(p)),
+                    }
+                }
+
+                problem
+            }
+            FieldKind::HexColour => {
+                let valid = value.len() == 7
+                    && value.starts_with('#')
+                    && value[1..].chars().all(|c| c.is_ascii_hexdigit());
+
+                if valid {
+                    None
+                } else {
+                    Some("is not a hex colour".to_string())
+                }
+            }
+            FieldKind::Enum(options) => {
+                if options.iter().any(|option| option == value) {
+                    None
+                } else {
+                    Some(format!("is not one of {}", options.join(", ")))
+                }
+            }
+            FieldKind::Digits(len) => {
+                if value.len() == *len && value.chars().all(|c| c.is_ascii_digit()) {
+                    None
+                } else {
+                    Some(format!("is not {} digits", len))
+                }
+            }
+            FieldKind::Regex(regex) => {
+                if regex.is_match(value) {
+                    None
+                } else {
+                    Some(format!("does not match {}", regex.as_str()))
+                }
+            }
+        }
     }
+}
+
+fn range_problem(value: &str, min: u64, max: u64, unit: &str) -> Option<String> {
+    match value.parse::<u64>() {
+        Err(_) => Some("is not a number".to_string()),
+        Ok(x) if x < min => Some(format!("is below {}-{}{}", min, max, unit)),
+        Ok(x) if x > max => Some(format!("exceeds {}-{}{}", min, max, unit)),
+        Ok(_) => None,
+    }
+}
+
+fn parse_range(range: &str) -> (u64, u64) {
+    let (min, max) = range.split_at(range.find('-').unwrap());
+
+    (min.parse().unwrap(), max[1..].parse().unwrap())
+}
+
+fn columns(passports: &[Passport], schema: &Schema) -> Vec<String> {
+    let mut columns: Vec<String> = schema.fields.iter().map(|rule| rule.name.clone()).collect();
+
+    let mut extra: Vec<&str> = passports
+        .iter()
+        .flat_map(|passport| passport.fields.keys().copied())
+        .filter(|&k| !columns.iter().any(|c| c == k))
+        .collect();
+
+    extra.sort_unstable();
+    extra.dedup();
+
+    columns.extend(extra.into_iter().map(str::to_string));
+
+    columns
+}
+
+fn export_json(passports: &[Passport], schema: &Schema, with_validity: bool) -> String {
+    let columns = columns(passports, schema);
+
+    let objects: Vec<String> = passports
+        .iter()
+        .map(|passport| {
+            let mut pairs: Vec<String> = columns
+                .iter()
+                .filter_map(|column| {
+                    let field = passport.fields.get(column.as_str())?;
+
+                    Some(format!(
+                        "{}:{}",
+                        json::string(column),
+                        json::string(&field.value)
+                    ))
+                })
+                .collect();
+
+            if with_validity {
+                pairs.push(format!("\"valid\":{}", passport.is_valid_for(schema)));
+            }
+
+            format!("  {{{}}}", pairs.join(","))
+        })
+        .collect();
+
+    format!("[\n{}\n]\n", objects.join(",\n"))
+}
+
+fn export_csv(passports: &[Passport], schema: &Schema, with_validity: bool) -> String {
+    let columns = columns(passports, schema);
 
-    fn height_valid(&self) -> bool {
-        self.fields
-            .get("hgt")
-            .and_then(|s| {
-                let (num, unit) = s.split_at(s.len() - 2);
+    let mut header: Vec<&str> = columns.iter().map(|c| c.as_str()).collect();
 
-                let num: u64 = num.parse().ok()?;
+    if with_validity {
+        header.push("valid");
+    }
 
-                let valid = match unit {
-                    "cm" => num >= 150 && num <= 193,
-                    "in" => num >= 59 && num <= 76,
-                    _ => false,
-                };
+    let mut out = csv::row(header);
+    out.push('\n');
 
-                Some(valid)
+    for passport in passports {
+        let mut cells: Vec<&str> = columns
+            .iter()
+            .map(|column| {
+                passport
+                    .fields
+                    .get(column.as_str())
+                    .map_or("", |f| &f.value)
             })
-            .unwrap_or(false)
+            .collect();
+
+        if with_validity {
+            cells.push(if passport.is_valid_for(schema) {
+                "true"
+            } else {
+                "false"
+            });
+        }
+
+        out += &csv::row(cells);
+        out.push('\n');
+    }
+
+    out
+}
+
+fn import_json(text: &str) -> Result<String, ImportError> {
+    let passports = match json::parse(text).map_err(ImportError::Json)? {
+        Value::Array(items) => items,
+        _ => return Err(ImportError::NotAnArray),
+    };
+
+    let mut batch = Vec::new();
+
+    for (i, passport) in passports.into_iter().enumerate() {
+        let fields = match passport {
+            Value::Object(fields) => fields,
+            _ => return Err(ImportError::NotAnObject { passport: i + 1 }),
+        };
+
+        let mut pairs = Vec::new();
+
+        for (k, v) in fields.into_iter().filter(|(k, _)| k != "valid") {
+            let value = match v {
+                Value::String(s) | Value::Number(s) => s,
+                Value::Bool(b) => b.to_string(),
+                Value::Null => continue,
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(ImportError::Field {
+                        passport: i + 1,
+                        field: k,
+                        problem: "has an array or object value",
+                    })
+                }
+            };
+
+            pairs.push((k, value));
+        }
+
+        batch.push(pairs);
+    }
+
+    to_batch(&batch)
+}
+
+fn import_csv(text: &str) -> Result<String, ImportError> {
+    let mut rows = csv::parse(text).into_iter();
+
+    let header = rows.next().unwrap_or_default();
+
+    let passports: Vec<Vec<(String, String)>> = rows
+        .map(|row| {
+            header
+                .iter()
+                .zip(row)
+                .filter(|(k, v)| *k != "valid" && !v.is_empty())
+                .map(|(k, v)| (k.clone(), v))
+                .collect()
+        })
+        .collect();
+
+    to_batch(&passports)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ImportError {
+    Json(json::Error),
+    NotAnArray,
+    NotAnObject {
+        passport: usize,
+    },
+    Field {
+        passport: usize,
+        field: String,
+        problem: &'static str,
+    },
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Json(e) => write!(f, "{}", e),
+            ImportError::NotAnArray => write!(f, "expected an array of passports"),
+            ImportError::NotAnObject { passport } => {
+                write!(f, "passport {}: expected an object of fields", passport)
+            }
+            ImportError::Field {
+                passport,
+                field,
+                problem,
+            } => write!(f, "passport {}: field {:?} {}", passport, field, problem),
+        }
+    }
+}
+
+fn to_batch(passports: &[Vec<(String, String)>]) -> Result<String, ImportError> {
+    let mut records = Vec::new();
+
+    for (i, fields) in passports.iter().enumerate() {
+        let mut pairs = Vec::new();
+
+        for (k, v) in fields {
+            let problem = if k.is_empty() || k.contains(|c: char| c == ':' || c.is_whitespace()) {
+                Some("is not a valid field name")
+            } else if v.is_empty() {
+                Some("has an empty value")
+            } else if v.contains(char::is_whitespace) {
+                Some("has whitespace in its value")
+            } else {
+                None
+            };
+
+            if let Some(problem) = problem {
+                return Err(ImportError::Field {
+                    passport: i + 1,
+                    field: k.clone(),
+                    problem,
+                });
+            }
+
+            pairs.push(format!("{}:{}", k, v));
+        }
+
+        records.push(pairs.join(" "));
     }
 
-    fn hcl_valid(&self) -> bool {
-        self.fields
-            .get("hcl")
-            .and_then(|&s| {
-                let mut chars = s.chars();
+    Ok(records.join("\n\n") + "\n")
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Fix {
+    AddUnit,
+    AddHash,
+    StripLeadingZero,
+}
+
+impl Fix {
+    pub const ALL: [Fix; 3] = [Fix::AddUnit, Fix::AddHash, Fix::StripLeadingZero];
+
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "add-unit" => Fix::AddUnit,
+            "add-hash" => Fix::AddHash,
+            "strip-leading-zero" => Fix::StripLeadingZero,
+            _ => panic!("Unknown fix: {}", name),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Fix::AddUnit => "add-unit",
+            Fix::AddHash => "add-hash",
+            Fix::StripLeadingZero => "strip-leading-zero",
+        }
+    }
+
+    pub fn apply(&self, kind: &FieldKind, value: &str) -> Option<String> {
+        match (self, kind) {
+            (Fix::AddUnit, FieldKind::UnitRange(units)) => {
+                let x: u64 = value.parse().ok()?;
+
+                let mut fitting = units.iter().filter(|(_, min, max)| x >= *min && x <= *max);
+
+                match (fitting.next(), fitting.next()) {
+                    (Some((unit, _, _)), None) => Some(format!("{}{}", value, unit)),
+                    _ => None,
+                }
+            }
+            (Fix::AddHash, FieldKind::HexColour) => {
+                if value.len() == 6 && value.chars().all(|c| c.is_ascii_hexdigit()) {
+                    Some(format!("#{}", value))
+                } else {
+                    None
+                }
+            }
+            (Fix::StripLeadingZero, FieldKind::Digits(len)) => {
+                let valid = value.len() == len + 1
+                    && value.starts_with('0')
+                    && value.chars().all(|c| c.is_ascii_digit());
+
+                if valid {
+                    Some(value[1..].to_string())
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Change {
+    field: String,
+    from: String,
+    to: String,
+    fix: Fix,
+    line: usize,
+    column: usize,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {} {:?} -> {:?} ({})",
+            self.line,
+            self.column,
+            self.field,
+            self.from,
+            self.to,
+            self.fix.name()
+        )
+    }
+}
+
+impl<'a> Passport<'a> {
+    pub fn normalised(&self, schema: &Schema, fixes: &[Fix]) -> (Passport<'a>, Vec<Change>) {
+        let mut passport = self.clone();
+        let mut changes = Vec::new();
+
+        for rule in &schema.fields {
+            let field = match passport.fields.get_mut(rule.name.as_str()) {
+                Some(field) if !rule.kind.accepts(&field.value) => field,
+                _ => continue,
+            };
+
+            let fixed = fixes.iter().find_map(|&fix| {
+                let value = fix.apply(&rule.kind, &field.value)?;
+
+                if rule.kind.accepts(&value) {
+                    Some((fix, value))
+                } else {
+                    None
+                }
+            });
+
+            if let Some((fix, value)) = fixed {
+                changes.push(Change {
+                    field: rule.name.clone(),
+                    from: field.value.to_string(),
+                    to: value.clone(),
+                    fix,
+                    line: field.line,
+                    column: field.column,
+                });
+
+                field.value = Cow::Owned(value);
+            }
+        }
+
+        (passport, changes)
+    }
+}
+
+#[derive(Debug)]
+struct NormalisedPassport {
+    lines: RangeInclusive<usize>,
+    changes: Vec<Change>,
+    valid_before: bool,
+    valid_after: bool,
+}
+
+#[derive(Debug)]
+struct NormalisationReport {
+    passports: Vec<NormalisedPassport>,
+}
+
+impl NormalisationReport {
+    pub fn of(passports: &[Passport], schema: &Schema, fixes: &[Fix]) -> Self {
+        let passports = passports
+            .iter()
+            .map(|passport| {
+                let (normalised, changes) = passport.normalised(schema, fixes);
 
-                Some(s.len() == 7 && chars.next()? == '#' && chars.all(|c| c.is_ascii_hexdigit()))
+                NormalisedPassport {
+                    lines: passport.lines.clone(),
+                    changes,
+                    valid_before: passport.is_valid_for(schema),
+                    valid_after: normalised.is_valid_for(schema),
+                }
             })
-            .unwrap_or(false)
+            .collect();
+
+        NormalisationReport { passports }
     }
 
-    fn ecl_valid(&self) -> bool {
-        self.fields
-            .get("ecl")
-            .map(|&s| matches!(s, "amb" | "blu" | "brn" | "gry" | "grn" | "hzl" | "oth"))
-            .unwrap_or(false)
+    pub fn newly_valid(&self) -> impl Iterator<Item = &NormalisedPassport> {
+        self.passports
+            .iter()
+            .filter(|passport| passport.valid_after && !passport.valid_before)
     }
 
-    fn pid_valid(&self) -> bool {
-        self.fields
-            .get("pid")
-            .map(|&s| s.len() == 9 && s.chars().all(|c| c.is_ascii_digit()))
-            .unwrap_or(false)
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        for passport in self.passports.iter().filter(|p| !p.changes.is_empty()) {
+            out += &format!(
+                "Passport on lines {}-{}{}:\n",
+                passport.lines.start(),
+                passport.lines.end(),
+                if passport.valid_after && !passport.valid_before {
+                    " (valid only after fixes)"
+                } else {
+                    ""
+                }
+            );
+
+            for change in &passport.changes {
+                out += &format!("  {}\n", change);
+            }
+        }
+
+        let num_changes: usize = self.passports.iter().map(|p| p.changes.len()).sum();
+        let num_changed = self
+            .passports
+            .iter()
+            .filter(|p| !p.changes.is_empty())
+            .count();
+
+        out += &format!(
+            "\nFixed {} fields in {} passports, {} passports are valid only after fixes\n",
+            num_changes,
+            num_changed,
+            self.newly_valid().count()
+        );
+
+        out
     }
 }
 
-fn split_key_value(pair: &str) -> (&str, &str) {
-    let (k, v) = pair.split_at(pair.find(':').unwrap());
+fn split_key_value(pair: &str) -> Option<(&str, &str)> {
+    let (k, v) = pair.split_at(pair.find(':')?);
 
-    (k, &v[1..])
+    Some((k, &v[1..]))
 }
 
 #[cfg(test)]
@@ -185,4 +1077,257 @@ iyr:2010 hgt:158cm hcl:#b6652a ecl:blu byr:1944 eyr:2021 pid:093154719
 
         assert_eq!(part_two(&parse_passports(valid)), 4);
     }
+
+    #[test]
+    fn custom_schema() {
+        let schema = Schema::parse(
+            "
+# Only a name and an optional five letter code
+name required regex [A-Z][a-z]+
+code optional regex [a-z]{5}
+age optional int 0-130
+",
+        );
+
+        let passports = parse_passports("name:Alice code:abcde\n\nname:bob\n\nname:Carol age:200");
+
+        let valid: Vec<bool> = passports
+            .iter()
+            .map(|passport| passport.is_valid_for(&schema))
+            .collect();
+
+        assert_eq!(valid, vec![true, false, false]);
+    }
+
+    #[test]
+    fn overlapping_units() {
+        let schema = Schema::parse("hgt required unit 1-3m 150-193cm");
+
+        let problems = |batch| -> Vec<String> {
+            parse_passports(batch)[0]
+                .problems(&schema)
+                .iter()
+                .map(|problem| problem.to_string())
+                .collect()
+        };
+
+        assert!(problems("hgt:170cm").is_empty());
+        assert!(problems("hgt:2m").is_empty());
+        assert_eq!(
+            problems("hgt:200cm"),
+            vec!["hgt: \"200cm\" exceeds 150-193cm"]
+        );
+        assert_eq!(problems("hgt:5m"), vec!["hgt: \"5m\" exceeds 1-3m"]);
+    }
+
+    #[test]
+    fn passport_problems() {
+        let passports = parse_passports(
+            "
+hgt:190in ecl:zzz
+byr:1900 pid:0123 hcl:dab227 foo:bar
+
+iyr:2015 cid:1
+"
+            .trim(),
+        );
+
+        let schema = Schema::default();
+
+        let problems: Vec<String> = passports[0]
+            .problems(&schema)
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect();
+
+        assert_eq!(
+            problems,
+            vec![
+                "byr: \"1900\" is below 1920-2002",
+                "iyr: missing",
+                "eyr: missing",
+                "hgt: \"190in\" exceeds 59-76in",
+                "hcl: \"dab227\" is not a hex colour",
+                "ecl: \"zzz\" is not one of amb, blu, brn, gry, grn, hzl, oth",
+                "pid: \"0123\" is not 9 digits",
+                "foo: unknown field",
+            ]
+        );
+
+        assert_eq!(passports[0].lines, 1..=2);
+        assert_eq!(passports[1].lines, 4..=4);
+
+        let report = BatchReport::of(&passports, &schema);
+
+        assert_eq!(report.num_valid(), 0);
+        assert_eq!(report.summary()["missing eyr"], 2);
+        assert_eq!(report.summary()["invalid hgt"], 1);
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let input = TEST_INPUT.trim();
+        let passports = parse_passports(input);
+        let schema = Schema::default();
+
+        let values = |passports: &[Passport]| -> Vec<BTreeMap<String, String>> {
+            passports
+                .iter()
+                .map(|passport| {
+                    passport
+                        .fields
+                        .iter()
+                        .map(|(k, field)| (k.to_string(), field.value.to_string()))
+                        .collect()
+                })
+                .collect()
+        };
+
+        let json = export_json(&passports, &schema, true);
+        let csv = export_csv(&passports, &schema, true);
+
+        assert!(json.starts_with(
+            "[\n  {\"byr\":\"1937\",\"iyr\":\"2017\",\"eyr\":\"2020\",\"hgt\":\"183cm\",\"hcl\":\"#fffffd\",\
+             \"ecl\":\"gry\",\"pid\":\"860033327\",\"cid\":\"147\",\"valid\":true},\n"
+        ));
+        assert_eq!(
+            csv.lines().take(2).collect::<Vec<_>>(),
+            vec![
+                "byr,iyr,eyr,hgt,hcl,ecl,pid,cid,valid",
+                "1937,2017,2020,183cm,#fffffd,gry,860033327,147,true"
+            ]
+        );
+
+        let from_json = import_json(&json).unwrap();
+        let from_csv = import_csv(&csv).unwrap();
+
+        assert_eq!(values(&parse_passports(&from_json)), values(&passports));
+        assert_eq!(values(&parse_passports(&from_csv)), values(&passports));
+    }
+
+    #[test]
+    fn import_rejects_values_that_do_not_round_trip() {
+        let error = |passport, field: &str, problem| {
+            Err(ImportError::Field {
+                passport,
+                field: field.to_string(),
+                problem,
+            })
+        };
+
+        assert_eq!(
+            import_json(r#"[{"byr":"1937"}, {"byr":"19 37"}]"#),
+            error(2, "byr", "has whitespace in its value")
+        );
+        assert_eq!(
+            import_json(r#"[{"byr":""}]"#),
+            error(1, "byr", "has an empty value")
+        );
+        assert_eq!(
+            import_json(r#"[{"b r":"1937"}]"#),
+            error(1, "b r", "is not a valid field name")
+        );
+        assert_eq!(
+            import_csv("byr,hgt\n1937,183 cm\n"),
+            error(1, "hgt", "has whitespace in its value")
+        );
+        assert_eq!(
+            import_csv("byr,hgt\n1937,\n").unwrap(),
+            import_json(r#"[{"byr":"1937","hgt":null}]"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn import_keeps_numbers_and_rejects_bad_json() {
+        assert_eq!(
+            import_json(r#"[{"pid": 012345678, "cid": 123456789012345678901}]"#),
+            Ok("pid:012345678 cid:123456789012345678901\n".to_string())
+        );
+
+        let error = |text| import_json(text).unwrap_err().to_string();
+
+        assert_eq!(
+            error(r#"[{"a": }]"#),
+            "invalid JSON at byte 7: unexpected '}'"
+        );
+        assert_eq!(
+            error(r#"{"byr": "1937"}"#),
+            "expected an array of passports"
+        );
+        assert_eq!(
+            error(r#"[{"byr": "1937"}, "iyr:2017"]"#),
+            "passport 2: expected an object of fields"
+        );
+        assert_eq!(
+            error(r#"[{"byr": ["1937"]}]"#),
+            "passport 1: field \"byr\" has an array or object value"
+        );
+    }
+
+    #[test]
+    fn normalise_near_misses() {
+        let input = "
+hgt:170 hcl:dab227 pid:0123456789 byr:1980 iyr:2015 eyr:2025 ecl:brn
+
+hgt:100 hcl:#123abc pid:1123456789 byr:1980 iyr:2015 eyr:2025 ecl:brn
+"
+        .trim();
+
+        let passports = parse_passports(input);
+        let schema = Schema::default();
+
+        let (normalised, changes) = passports[0].normalised(&schema, &Fix::ALL);
+
+        let changes: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+
+        assert_eq!(
+            changes,
+            vec![
+                "line 1, column 1: hgt \"170\" -> \"170cm\" (add-unit)",
+                "line 1, column 9: hcl \"dab227\" -> \"#dab227\" (add-hash)",
+                "line 1, column 20: pid \"0123456789\" -> \"123456789\" (strip-leading-zero)",
+            ]
+        );
+
+        assert!(normalised.is_valid_for(&schema));
+        assert!(!passports[0].is_valid_for(&schema));
+        assert_eq!(passports[0].fields["hgt"].value, "170");
+
+        let (_, changes) = passports[1].normalised(&schema, &Fix::ALL);
+        assert!(changes.is_empty());
+
+        let (_, changes) = passports[0].normalised(&schema, &[Fix::AddHash]);
+        assert_eq!(changes.len(), 1);
+
+        let report = NormalisationReport::of(&passports, &schema, &Fix::ALL);
+        let newly_valid: Vec<_> = report.newly_valid().map(|p| p.lines.clone()).collect();
+
+        assert_eq!(newly_valid, vec![1..=1]);
+    }
+
+    #[test]
+    fn parse_issues() {
+        let input = "byr:1980 iyr:2015\r\n  junk byr:1990\r\n \t \r\necl:brn\r\n";
+
+        let passports = parse_passports(input);
+
+        assert_eq!(passports.len(), 2);
+        assert_eq!(passports[0].lines, 1..=2);
+        assert_eq!(passports[1].lines, 4..=4);
+        assert_eq!(passports[0].fields["byr"].value, "1980");
+
+        let issues: Vec<String> = passports[0]
+            .issues
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect();
+
+        assert_eq!(
+            issues,
+            vec![
+                "line 2, column 3: \"junk\" is not a key:value pair",
+                "line 2, column 8: duplicate byr \"1990\" ignored, keeping \"1980\" from line 1, column 1",
+            ]
+        );
+    }
 }
@@ -1,91 +1,662 @@
-use super::utils::start_day;
-use std::collections::HashMap;
+use super::json;
+use super::utils::{option, read_input_file, start_day};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt::{self, Write};
 
 pub fn main() {
     let input = start_day("seven");
 
     let bags = Bags::parse(&input);
 
-    println!("Part one: {}", bags.num_gold_bag_containers());
-    println!("Part two: {}", bags.bags_within_bag("shiny gold"));
+    println!("Part one: {}", bags.num_containers("shiny gold"));
+
+    match bags.bags_within_bag("shiny gold") {
+        Ok(count) => println!("Part two: {}", count),
+        Err(e) => println!("Part two: {}", e),
+    }
+
     println!();
 }
 
+pub fn check(args: &[String]) {
+    let input = read_rules(args);
+    let problems = Bags::parse(&input).validate();
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    println!("{} problems found", problems.len());
+}
+
+pub fn query(args: &[String]) {
+    let input = read_rules(args);
+
+    let mut bags = Bags::parse(input.trim());
+
+    for arg in args {
+        if let Some(rule) = arg.strip_prefix("--set=") {
+            bags.set_rule(rule);
+        } else if let Some(bag) = arg.strip_prefix("--remove=") {
+            bags.remove_rule(bag);
+        }
+    }
+
+    for problem in bags.validate() {
+        eprintln!("warning: {}", problem);
+    }
+
+    let args: Vec<&str> = args
+        .iter()
+        .filter(|a| !a.starts_with("--"))
+        .map(String::as_str)
+        .collect();
+
+    match args.as_slice() {
+        ["ancestors", bag] => {
+            let ancestors = bags.ancestors(bag);
+
+            println!("{} bags can contain {}:", ancestors.len(), bag);
+
+            for ancestor in ancestors {
+                println!("  {}", ancestor);
+            }
+        }
+        ["descendants", bag] => {
+            let (total, descendants) = match (bags.bags_within_bag(bag), bags.descendants(bag)) {
+                (Ok(total), Ok(descendants)) => (total, descendants),
+                (Err(e), _) | (_, Err(e)) => {
+                    println!("{} contains {}", bag, e);
+                    return;
+                }
+            };
+
+            println!("{} contains {} bags:", bag, total);
+
+            for (descendant, count) in descendants {
+                println!("  {} {}", count, descendant);
+            }
+        }
+        ["contains", outer, inner] => {
+            if bags.can_contain(outer, inner) {
+                println!("{} can contain {}", outer, inner);
+            } else {
+                println!("{} cannot contain {}", outer, inner);
+            }
+        }
+        ["paths", outer, inner] => {
+            for path in bags.paths(outer, inner) {
+                println!("{}", path.join(" -> "));
+            }
+        }
+        _ => {
+            eprintln!("Usage: bag-query [OPTIONS] ancestors|descendants BAG");
+            eprintln!("       bag-query [OPTIONS] contains|paths OUTER INNER");
+            eprintln!("Options: --input=FILE --set=RULE --remove=BAG");
+            std::process::exit(1);
+        }
+    }
+}
+
+pub fn dot(args: &[String]) {
+    let input = read_rules(args);
+    let bags = Bags::parse(input.trim());
+
+    let focus = args.iter().find_map(|a| {
+        if let Some(bag) = a.strip_prefix("--ancestors=") {
+            Some(Focus::Ancestors(bag))
+        } else {
+            a.strip_prefix("--descendants=").map(Focus::Descendants)
+        }
+    });
+
+    let depth_colours = args.iter().any(|a| a == "--depth-colours");
+
+    print!("{}", bags.to_dot(focus, depth_colours));
+}
+
+fn read_rules(args: &[String]) -> String {
+    match option(args, "input") {
+        Some(path) => std::fs::read_to_string(path).unwrap(),
+        None => read_input_file("seven"),
+    }
+}
+
 struct Bags<'a> {
     bags: HashMap<&'a str, Vec<(usize, &'a str)>>,
+    lines: HashMap<&'a str, usize>,
+    duplicates: Vec<(&'a str, usize)>,
+    containers: HashMap<&'a str, Vec<&'a str>>,
+    totals: RefCell<HashMap<&'a str, usize>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RuleProblem<'a> {
+    Cycle(Vec<&'a str>),
+    Undefined {
+        bag: &'a str,
+        container: &'a str,
+        line: usize,
+    },
+    Duplicate {
+        bag: &'a str,
+        first: usize,
+        line: usize,
+    },
+}
+
+impl<'a> fmt::Display for RuleProblem<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuleProblem::Cycle(path) => write!(f, "cycle: {}", path.join(" -> ")),
+            RuleProblem::Undefined {
+                bag,
+                container,
+                line,
+            } => write!(
+                f,
+                "line {}: {} contains {}, which has no rule",
+                line, container, bag
+            ),
+            RuleProblem::Duplicate { bag, first, line } => write!(
+                f,
+                "line {}: {} is already defined on line {}, keeping the first rule",
+                line, bag, first
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Focus<'b> {
+    Ancestors(&'b str),
+    Descendants(&'b str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CountError<'a> {
+    Infinite(Vec<&'a str>),
+    Overflow(&'a str),
+}
+
+impl<'a> fmt::Display for CountError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CountError::Infinite(cycle) => write!(
+                f,
+                "infinitely many bags, because of the cycle {}",
+                cycle.join(" -> ")
+            ),
+            CountError::Overflow(bag) => write!(
+                f,
+                "more bags than fit in a usize, overflowing inside {}",
+                bag
+            ),
+        }
+    }
 }
 
 impl<'a> Bags<'a> {
     pub fn parse(input: &'a str) -> Self {
-        let bags = input
-            .lines()
-            .map(|l| {
-                let contain = " bags contain ";
-                let mid_point = l.find(contain).unwrap();
+        let mut bags = HashMap::new();
+        let mut lines = HashMap::new();
+        let mut duplicates = Vec::new();
+
+        for (i, l) in input.lines().enumerate() {
+            let (name, inner_bags) = parse_rule(l);
+
+            if lines.contains_key(name) {
+                duplicates.push((name, i + 1));
+            } else {
+                bags.insert(name, inner_bags);
+                lines.insert(name, i + 1);
+            }
+        }
+
+        let mut bags = Bags {
+            bags,
+            lines,
+            duplicates,
+            containers: HashMap::new(),
+            totals: RefCell::new(HashMap::new()),
+        };
+
+        let rules: Vec<(&'a str, Vec<(usize, &'a str)>)> = bags
+            .bags
+            .iter()
+            .map(|(&bag, inner)| (bag, inner.clone()))
+            .collect();
+
+        for (bag, inner_bags) in rules {
+            bags.link(bag, &inner_bags);
+        }
 
-                let name = &l[..mid_point];
+        bags
+    }
+
+    pub fn set_rule(&mut self, rule: &'a str) {
+        let (bag, inner_bags) = parse_rule(rule);
+
+        self.remove_rule(bag);
+
+        let next_line = self.lines.values().max().map_or(1, |line| line + 1);
+
+        self.link(bag, &inner_bags);
+        self.bags.insert(bag, inner_bags);
+        self.lines.insert(bag, next_line);
+    }
+
+    pub fn remove_rule(&mut self, bag: &str) -> bool {
+        self.invalidate(bag);
+
+        let (bag, inner_bags) = match self.bags.remove_entry(bag) {
+            Some(rule) => rule,
+            None => return false,
+        };
+
+        for (_, inner_bag) in inner_bags {
+            if let Some(containers) = self.containers.get_mut(inner_bag) {
+                containers.retain(|&container| container != bag);
+
+                if containers.is_empty() {
+                    self.containers.remove(inner_bag);
+                }
+            }
+        }
 
-                let contains = mid_point + contain.len();
+        self.lines.remove(bag);
+        self.duplicates.retain(|&(duplicate, _)| duplicate != bag);
 
-                let inner_bags = match &l[contains..] {
-                    "no other bags." => vec![],
-                    rest => parse_inner_bags(rest),
-                };
+        true
+    }
+
+    fn link(&mut self, bag: &'a str, inner_bags: &[(usize, &'a str)]) {
+        for &(_, inner_bag) in inner_bags {
+            self.containers.entry(inner_bag).or_default().push(bag);
+        }
+    }
+
+    fn invalidate(&self, bag: &str) {
+        let mut totals = self.totals.borrow_mut();
+
+        totals.remove(bag);
 
-                (name, inner_bags)
+        for ancestor in self.ancestors(bag) {
+            totals.remove(ancestor);
+        }
+    }
+
+    pub fn validate(&self) -> Vec<RuleProblem<'a>> {
+        let mut problems: Vec<RuleProblem> = self
+            .duplicates
+            .iter()
+            .map(|&(bag, line)| RuleProblem::Duplicate {
+                bag,
+                first: self.lines[bag],
+                line,
             })
             .collect();
 
-        Bags { bags }
+        let mut bags: Vec<&'a str> = self.bags.keys().copied().collect();
+        bags.sort_by_key(|bag| self.lines[bag]);
+
+        for &container in &bags {
+            for &(_, bag) in self.inner_bags(container) {
+                if !self.bags.contains_key(bag) {
+                    problems.push(RuleProblem::Undefined {
+                        bag,
+                        container,
+                        line: self.lines[container],
+                    });
+                }
+            }
+        }
+
+        let mut finished = HashSet::new();
+
+        for bag in bags {
+            for cycle in self.cycles_from(bag, &mut finished) {
+                problems.push(RuleProblem::Cycle(cycle));
+            }
+        }
+
+        problems
     }
 
-    pub fn num_gold_bag_containers(&self) -> usize {
-        let mut gold_bags_per_bag = HashMap::with_capacity(self.bags.len());
+    fn cycles_from(&self, bag: &'a str, finished: &mut HashSet<&'a str>) -> Vec<Vec<&'a str>> {
+        let mut cycles = Vec::new();
 
-        self.bags
-            .keys()
-            .map(|bag| self.gold(bag, &mut gold_bags_per_bag))
-            .filter(|&x| x > 0)
-            .count()
+        self.find_cycles(bag, &mut Vec::new(), finished, &mut cycles);
+
+        cycles
     }
 
-    fn gold(&self, bag: &'a str, mut gold_bags_per_bag: &mut HashMap<&'a str, usize>) -> usize {
-        gold_bags_per_bag.get(bag).copied().unwrap_or_else(|| {
-            let gold_bags = self.bags[bag]
-                .iter()
-                .map(|&(count, inner_bag)| {
-                    if inner_bag == "shiny gold" {
-                        return count;
-                    }
+    fn find_cycles(
+        &self,
+        bag: &'a str,
+        path: &mut Vec<&'a str>,
+        finished: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<&'a str>>,
+    ) {
+        if finished.contains(bag) {
+            return;
+        }
+
+        if let Some(start) = path.iter().position(|&b| b == bag) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(bag);
+
+            cycles.push(cycle);
+            return;
+        }
+
+        path.push(bag);
+
+        for &(_, inner_bag) in self.inner_bags(bag) {
+            self.find_cycles(inner_bag, path, finished, cycles);
+        }
+
+        path.pop();
+        finished.insert(bag);
+    }
 
-                    let gold_bags = count * self.gold(inner_bag, &mut gold_bags_per_bag);
+    fn inner_bags(&self, bag: &str) -> &[(usize, &'a str)] {
+        self.bags.get(bag).map_or(&[], Vec::as_slice)
+    }
 
-                    gold_bags_per_bag.insert(inner_bag, gold_bags);
+    pub fn num_containers(&self, bag: &str) -> usize {
+        self.ancestors(bag).len()
+    }
 
-                    gold_bags
-                })
-                .sum();
+    pub fn ancestors(&self, bag: &str) -> BTreeSet<&'a str> {
+        let mut ancestors = BTreeSet::new();
+        let mut to_visit: Vec<&str> = vec![bag];
 
-            gold_bags
-        })
+        while let Some(bag) = to_visit.pop() {
+            for &container in self.containers.get(bag).into_iter().flatten() {
+                if ancestors.insert(container) {
+                    to_visit.push(container);
+                }
+            }
+        }
+
+        ancestors
+    }
+
+    pub fn descendants(&self, bag: &str) -> Result<BTreeMap<&'a str, usize>, CountError<'a>> {
+        let bag = match self.bags.get_key_value(bag) {
+            Some((&bag, _)) => bag,
+            None => return Ok(BTreeMap::new()),
+        };
+
+        if let Some(cycle) = self
+            .cycles_from(bag, &mut HashSet::new())
+            .into_iter()
+            .next()
+        {
+            return Err(CountError::Infinite(cycle));
+        }
+
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+
+        self.post_order(bag, &mut visited, &mut order);
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        counts.insert(bag, 1);
+
+        for &outer in order.iter().rev() {
+            let outer_count = counts.get(outer).copied().unwrap_or_default();
+
+            for &(count, inner_bag) in self.inner_bags(outer) {
+                let inner_count = counts.entry(inner_bag).or_default();
+
+                *inner_count = outer_count
+                    .checked_mul(count)
+                    .and_then(|n| n.checked_add(*inner_count))
+                    .ok_or(CountError::Overflow(outer))?;
+            }
+        }
+
+        Ok(order
+            .into_iter()
+            .filter(|&inner_bag| inner_bag != bag)
+            .map(|inner_bag| (inner_bag, counts[inner_bag]))
+            .collect())
     }
 
-    pub fn bags_within_bag(&self, bag: &str) -> usize {
-        let mut bags_per_bag: HashMap<&str, usize> = HashMap::with_capacity(self.bags.len());
+    fn post_order(&self, bag: &'a str, visited: &mut HashSet<&'a str>, order: &mut Vec<&'a str>) {
+        if !visited.insert(bag) {
+            return;
+        }
+
+        for &(_, inner_bag) in self.inner_bags(bag) {
+            self.post_order(inner_bag, visited, order);
+        }
 
-        self.num_bags_with_bag(bag, &mut bags_per_bag)
+        order.push(bag);
     }
 
-    fn num_bags_with_bag(&self, bag: &str, mut bags_per_bag: &mut HashMap<&str, usize>) -> usize {
-        bags_per_bag.get(bag).copied().unwrap_or_else(|| {
-            self.bags[bag]
-                .iter()
-                .map(|(count, inner_bag)| {
-                    count * (1 + self.num_bags_with_bag(inner_bag, &mut bags_per_bag))
-                })
-                .sum()
-        })
+    pub fn can_contain(&self, outer: &str, inner: &str) -> bool {
+        let mut visited = HashSet::new();
+        let mut to_visit = vec![outer];
+
+        while let Some(bag) = to_visit.pop() {
+            for &(_, inner_bag) in self.inner_bags(bag) {
+                if inner_bag == inner {
+                    return true;
+                }
+
+                if visited.insert(inner_bag) {
+                    to_visit.push(inner_bag);
+                }
+            }
+        }
+
+        false
+    }
+
+    pub fn paths(&self, outer: &str, inner: &str) -> Vec<Vec<&'a str>> {
+        let mut paths = Vec::new();
+
+        if let Some((&outer, _)) = self.bags.get_key_value(outer) {
+            self.find_paths(&mut vec![outer], inner, &mut paths);
+        }
+
+        paths
     }
+
+    fn find_paths(&self, path: &mut Vec<&'a str>, inner: &str, paths: &mut Vec<Vec<&'a str>>) {
+        let bag = path[path.len() - 1];
+
+        for &(_, inner_bag) in self.inner_bags(bag) {
+            if path.contains(&inner_bag) {
+                continue;
+            }
+
+            path.push(inner_bag);
+
+            if inner_bag == inner {
+                paths.push(path.clone());
+            } else {
+                self.find_paths(path, inner, paths);
+            }
+
+            path.pop();
+        }
+    }
+
+    pub fn to_dot(&self, focus: Option<Focus>, depth_colours: bool) -> String {
+        let containers = &self.containers;
+
+        let mut names: Vec<&'a str> = self
+            .bags
+            .keys()
+            .copied()
+            .chain(containers.keys().copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        names.sort_by_key(|bag| self.lines.get(bag).copied().unwrap_or(usize::MAX));
+
+        let depths = match focus {
+            Some(Focus::Ancestors(bag)) => depths(vec![bag], |b| {
+                containers.get(b).cloned().unwrap_or_default()
+            }),
+            Some(Focus::Descendants(bag)) => depths(vec![bag], |b| {
+                self.inner_bags(b).iter().map(|&(_, inner)| inner).collect()
+            }),
+            None => depths(
+                names
+                    .iter()
+                    .copied()
+                    .filter(|bag| !containers.contains_key(bag))
+                    .collect(),
+                |b| self.inner_bags(b).iter().map(|&(_, inner)| inner).collect(),
+            ),
+        };
+
+        let included = |bag: &str| focus.is_none() || depths.contains_key(bag);
+        let max_depth = depths.values().copied().max().unwrap_or_default();
+
+        let mut out = String::from("digraph bags {\n");
+
+        for &bag in names.iter().filter(|&&bag| included(bag)) {
+            let mut attributes = Vec::new();
+
+            if let Some(Focus::Ancestors(focus) | Focus::Descendants(focus)) = focus {
+                if bag == focus {
+                    attributes.push("penwidth=3".to_string());
+                }
+            }
+
+            if !self.bags.contains_key(bag) {
+                attributes.push("style=dashed".to_string());
+            } else if let (true, Some(&depth)) = (depth_colours, depths.get(bag)) {
+                attributes.push(format!(
+                    "style=filled, fillcolor=\"{:.3} 0.400 1.000\"",
+                    depth as f64 / (max_depth + 1) as f64
+                ));
+            }
+
+            if attributes.is_empty() {
+                writeln!(out, "    {};", json::string(bag)).unwrap();
+            } else {
+                writeln!(
+                    out,
+                    "    {} [{}];",
+                    json::string(bag),
+                    attributes.join(", ")
+                )
+                .unwrap();
+            }
+        }
+
+        for &bag in names.iter().filter(|&&bag| included(bag)) {
+            for &(count, inner_bag) in self.inner_bags(bag) {
+                if !included(inner_bag) {
+                    continue;
+                }
+
+                writeln!(
+                    out,
+                    "    {} -> {} [label=\"{}\"];",
+                    json::string(bag),
+                    json::string(inner_bag),
+                    count
+                )
+                .unwrap();
+            }
+        }
+
+        out.push_str("}\n");
+
+        out
+    }
+
+    pub fn bags_within_bag(&self, bag: &str) -> Result<usize, CountError<'a>> {
+        let bag = match self.bags.get_key_value(bag) {
+            Some((&bag, _)) => bag,
+            None => return Ok(0),
+        };
+
+        self.num_bags_with_bag(bag, &mut Vec::new())
+    }
+
+    fn num_bags_with_bag(
+        &self,
+        bag: &'a str,
+        path: &mut Vec<&'a str>,
+    ) -> Result<usize, CountError<'a>> {
+        if let Some(&count) = self.totals.borrow().get(bag) {
+            return Ok(count);
+        }
+
+        if let Some(start) = path.iter().position(|&b| b == bag) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(bag);
+
+            return Err(CountError::Infinite(cycle));
+        }
+
+        path.push(bag);
+
+        let mut total: usize = 0;
+
+        for &(count, inner_bag) in self.inner_bags(bag) {
+            let inner_total = self.num_bags_with_bag(inner_bag, path)?;
+
+            total = inner_total
+                .checked_add(1)
+                .and_then(|n| n.checked_mul(count))
+                .and_then(|n| n.checked_add(total))
+                .ok_or(CountError::Overflow(bag))?;
+        }
+
+        path.pop();
+        self.totals.borrow_mut().insert(bag, total);
+
+        Ok(total)
+    }
+}
+
+fn depths<'a, F>(starts: Vec<&'a str>, next: F) -> HashMap<&'a str, usize>
+where
+    F: Fn(&str) -> Vec<&'a str>,
+{
+    let mut depths: HashMap<&str, usize> = starts.iter().map(|&bag| (bag, 0)).collect();
+    let mut to_visit: VecDeque<&str> = starts.into_iter().collect();
+
+    while let Some(bag) = to_visit.pop_front() {
+        let depth = depths[bag];
+
+        for next_bag in next(bag) {
+            if !depths.contains_key(next_bag) {
+                depths.insert(next_bag, depth + 1);
+                to_visit.push_back(next_bag);
+            }
+        }
+    }
+
+    depths
+}
+
+fn parse_rule(rule: &str) -> (&str, Vec<(usize, &str)>) {
+    let contain = " bags contain ";
+    let mid_point = rule.find(contain).unwrap();
+
+    let name = &rule[..mid_point];
+
+    let contains = mid_point + contain.len();
+
+    let inner_bags = match &rule[contains..] {
+        "no other bags." => vec![],
+        rest => parse_inner_bags(rest),
+    };
+
+    (name, inner_bags)
 }
 
 fn parse_inner_bags(list: &str) -> Vec<(usize, &str)> {
@@ -129,14 +700,14 @@ dotted black bags contain no other bags.
     fn sample_input_part_one() {
         let bags = Bags::parse(TEST_INPUT.trim());
 
-        assert_eq!(bags.num_gold_bag_containers(), 4);
+        assert_eq!(bags.num_containers("shiny gold"), 4);
     }
 
     #[test]
     fn sample_input_part_two() {
         let bags = Bags::parse(TEST_INPUT.trim());
 
-        assert_eq!(bags.bags_within_bag("shiny gold"), 32);
+        assert_eq!(bags.bags_within_bag("shiny gold"), Ok(32));
     }
 
     #[test]
@@ -154,6 +725,237 @@ dark violet bags contain no other bags.
 
         let bags = Bags::parse(input);
 
-        assert_eq!(bags.bags_within_bag("shiny gold"), 126);
+        assert_eq!(bags.bags_within_bag("shiny gold"), Ok(126));
+    }
+
+    #[test]
+    fn containment_queries() {
+        let bags = Bags::parse(TEST_INPUT.trim());
+
+        assert_eq!(
+            bags.ancestors("shiny gold").into_iter().collect::<Vec<_>>(),
+            vec!["bright white", "dark orange", "light red", "muted yellow"]
+        );
+        assert!(bags.ancestors("light red").is_empty());
+
+        let descendants = bags.descendants("shiny gold").unwrap();
+
+        assert_eq!(
+            descendants.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("dark olive", 1),
+                ("dotted black", 16),
+                ("faded blue", 13),
+                ("vibrant plum", 2)
+            ]
+        );
+        assert_eq!(
+            bags.descendants("light red").unwrap()["faded blue"],
+            9 * 2 + 13 * 5
+        );
+
+        assert!(bags.can_contain("light red", "dotted black"));
+        assert!(!bags.can_contain("shiny gold", "light red"));
+        assert!(!bags.can_contain("faded blue", "faded blue"));
+
+        assert_eq!(
+            bags.paths("light red", "shiny gold"),
+            vec![
+                vec!["light red", "bright white", "shiny gold"],
+                vec!["light red", "muted yellow", "shiny gold"],
+            ]
+        );
+        assert_eq!(bags.paths("light red", "dark orange").len(), 0);
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let input = "
+light red bags contain 1 bright white bag, 2 muted yellow bags.
+bright white bags contain 1 shiny gold bag.
+shiny gold bags contain 2 light red bags, 1 dark olive bag.
+muted yellow bags contain no other bags.
+light red bags contain 1 muted yellow bag.
+dark olive bags contain 3 dark olive bags.
+"
+        .trim();
+
+        let bags = Bags::parse(input);
+
+        assert_eq!(
+            bags.validate(),
+            vec![
+                RuleProblem::Duplicate {
+                    bag: "light red",
+                    first: 1,
+                    line: 5
+                },
+                RuleProblem::Cycle(vec!["light red", "bright white", "shiny gold", "light red"]),
+                RuleProblem::Cycle(vec!["dark olive", "dark olive"]),
+            ]
+        );
+
+        assert_eq!(
+            bags.bags_within_bag("bright white"),
+            Err(CountError::Infinite(vec![
+                "bright white",
+                "shiny gold",
+                "light red",
+                "bright white"
+            ]))
+        );
+        assert_eq!(
+            bags.descendants("dark olive"),
+            Err(CountError::Infinite(vec!["dark olive", "dark olive"]))
+        );
+        assert_eq!(bags.bags_within_bag("muted yellow"), Ok(0));
+        assert!(bags.can_contain("shiny gold", "shiny gold"));
+        assert_eq!(bags.ancestors("dark olive").len(), 4);
+    }
+
+    #[test]
+    fn undefined_bags() {
+        let bags = Bags::parse("light red bags contain 2 muted yellow bags, 1 faded blue bag.\nfaded blue bags contain no other bags.");
+
+        assert_eq!(
+            bags.validate(),
+            vec![RuleProblem::Undefined {
+                bag: "muted yellow",
+                container: "light red",
+                line: 1
+            }]
+        );
+        assert_eq!(bags.bags_within_bag("light red"), Ok(3));
+    }
+
+    #[test]
+    fn dot_export() {
+        let bags = Bags::parse(TEST_INPUT.trim());
+
+        let dot = bags.to_dot(None, false);
+
+        assert!(dot.starts_with("digraph bags {\n    \"light red\";\n"));
+        assert!(dot.contains("    \"muted yellow\" -> \"faded blue\" [label=\"9\"];\n"));
+        assert_eq!(dot.matches(" -> ").count(), 13);
+
+        let dot = bags.to_dot(Some(Focus::Ancestors("bright white")), true);
+
+        assert_eq!(
+            dot,
+            "digraph bags {
+    \"light red\" [style=filled, fillcolor=\"0.500 0.400 1.000\"];
+    \"dark orange\" [style=filled, fillcolor=\"0.500 0.400 1.000\"];
+    \"bright white\" [penwidth=3, style=filled, fillcolor=\"0.000 0.400 1.000\"];
+    \"light red\" -> \"bright white\" [label=\"1\"];
+    \"dark orange\" -> \"bright white\" [label=\"3\"];
+}
+"
+        );
+    }
+
+    fn doubling_chain(levels: usize) -> String {
+        (0..levels)
+            .map(|i| format!("tier {} bags contain 2 tier {} bags.\n", i, i + 1))
+            .chain(std::iter::once(format!(
+                "tier {} bags contain no other bags.",
+                levels
+            )))
+            .collect()
+    }
+
+    #[test]
+    fn deep_chains_report_overflow() {
+        let bits = usize::BITS as usize;
+        let tier = |n: usize| format!("tier {}", n);
+
+        let input = doubling_chain(bits - 4);
+        let bags = Bags::parse(&input);
+
+        assert_eq!(bags.bags_within_bag("tier 0"), Ok((1 << (bits - 3)) - 2));
+        assert_eq!(
+            bags.descendants("tier 0").unwrap()[tier(bits - 4).as_str()],
+            1 << (bits - 4)
+        );
+
+        let input = doubling_chain(500);
+        let bags = Bags::parse(&input);
+
+        assert_eq!(
+            bags.bags_within_bag("tier 0"),
+            Err(CountError::Overflow(tier(500 - bits).as_str()))
+        );
+        assert_eq!(
+            bags.descendants("tier 0"),
+            Err(CountError::Overflow(tier(bits - 1).as_str()))
+        );
+        assert_eq!(bags.bags_within_bag(&tier(501 - bits)), Ok(usize::MAX - 1));
+        assert!(bags.validate().is_empty());
+    }
+
+    #[test]
+    fn rule_updates_invalidate_ancestors() {
+        let mut bags = Bags::parse(TEST_INPUT.trim());
+
+        assert_eq!(bags.bags_within_bag("light red"), Ok(186));
+
+        let cached = |bags: &Bags, bag: &str| bags.totals.borrow().contains_key(bag);
+
+        bags.set_rule("dark olive bags contain 1 faded blue bag, 1 pale green bag.");
+
+        assert!(!cached(&bags, "dark olive"));
+        assert!(!cached(&bags, "shiny gold"));
+        assert!(!cached(&bags, "light red"));
+        assert!(cached(&bags, "vibrant plum"));
+        assert!(cached(&bags, "faded blue"));
+
+        assert_eq!(bags.bags_within_bag("shiny gold"), Ok(27));
+        assert_eq!(bags.num_containers("pale green"), 6);
+        assert_eq!(bags.validate().len(), 1);
+
+        bags.set_rule("pale green bags contain 2 faded blue bags.");
+        bags.remove_rule("vibrant plum");
+
+        assert_eq!(bags.bags_within_bag("shiny gold"), Ok(7));
+        assert!(!bags.can_contain("shiny gold", "dotted black"));
+        assert!(bags.ancestors("dotted black").is_empty());
+        assert_eq!(
+            bags.validate(),
+            vec![RuleProblem::Undefined {
+                bag: "vibrant plum",
+                container: "shiny gold",
+                line: 5
+            }]
+        );
+
+        assert!(!bags.remove_rule("vibrant plum"));
+        assert_eq!(bags.num_containers("shiny gold"), 4);
+    }
+
+    #[test]
+    fn rule_updates_forget_duplicates() {
+        let rules = "light red bags contain no other bags.
+light red bags contain no other bags.
+bright white bags contain 1 light red bag.";
+
+        let mut bags = Bags::parse(rules);
+
+        assert_eq!(bags.validate().len(), 1);
+
+        bags.set_rule("light red bags contain 2 faded blue bags.");
+        bags.set_rule("faded blue bags contain no other bags.");
+
+        assert!(bags.validate().is_empty());
+
+        let mut bags = Bags::parse(rules);
+
+        assert!(bags.remove_rule("light red"));
+        assert_eq!(
+            bags.validate(),
+            vec![RuleProblem::Undefined {
+                bag: "light red",
+                container: "bright white",
+                line: 3
+            }]
+        );
     }
 }
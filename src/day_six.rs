@@ -1,4 +1,8 @@
-use super::utils::start_day;
+use super::records::{blank_line_groups, Record};
+use super::utils::{read_input_arg, start_day};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::iter::FromIterator;
 
 pub fn main() {
     let input = start_day("six");
@@ -8,55 +12,266 @@ pub fn main() {
     println!();
 }
 
-fn part_one(input: &str) -> usize {
-    let groups = input.split("\n\n");
+pub fn query(args: &[String]) {
+    let input = read_input_arg(args, "six");
 
-    let mut result = 0;
+    let groups = parse_groups(&input);
+    let queries: Vec<Query> = args.iter().filter_map(|a| Query::parse(a)).collect();
 
-    for group in groups {
-        let mut responses = [false; 26];
+    if queries.is_empty() || args.iter().any(|a| a == "--frequency") {
+        println!("Question  People  Groups");
 
-        group
-            .chars()
-            .filter(|c| c.is_ascii_alphabetic())
-            .for_each(|c| {
-                let i = (c as usize) - 97;
+        for (question, frequency) in frequencies(&groups) {
+            println!(
+                "{:<8}  {:>6}  {:>6}",
+                question, frequency.people, frequency.groups
+            );
+        }
+    }
 
-                responses[i] = true;
-            });
+    for query in queries {
+        let answers: Vec<(&Group, AnswerSet)> = groups
+            .iter()
+            .map(|group| (group, query.answers(group)))
+            .filter(|(_, answers)| answers.len() > 0)
+            .collect();
 
-        result += responses.iter().filter(|&x| *x).count();
+        println!();
+        println!(
+            "{}: {} across {} groups",
+            query.describe(),
+            answers.iter().map(|(_, a)| a.len()).sum::<usize>(),
+            answers.len()
+        );
+
+        for (group, answers) in answers {
+            println!("  line {}: {}", group.line, answers);
+        }
     }
+}
 
-    result
+fn part_one(input: &str) -> usize {
+    parse_groups(input).iter().map(|g| g.anyone().len()).sum()
 }
 
 fn part_two(input: &str) -> usize {
-    let groups = input.split("\n\n");
+    parse_groups(input).iter().map(|g| g.everyone().len()).sum()
+}
 
-    let mut result = 0;
+fn parse_groups(input: &str) -> Vec<Group> {
+    blank_line_groups(input).iter().map(Group::parse).collect()
+}
 
-    for group in groups {
-        let mut responses: [u8; 26] = [0; 26];
-        let mut num_responders = 1;
-
-        for c in group.chars() {
-            match c {
-                '\n' => {
-                    num_responders += 1;
-                }
-                _ => {
-                    let i = (c as usize) - 97;
-
-                    responses[i] += 1;
-                }
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct AnswerSet {
+    ascii: u128,
+    other: HashSet<char>,
+}
+
+impl AnswerSet {
+    pub fn insert(&mut self, c: char) {
+        if c.is_ascii() {
+            self.ascii |= 1 << c as u32;
+        } else {
+            self.other.insert(c);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ascii.count_ones() as usize + self.other.len()
+    }
+
+    pub fn union(&self, other: &AnswerSet) -> AnswerSet {
+        AnswerSet {
+            ascii: self.ascii | other.ascii,
+            other: self.other.union(&other.other).copied().collect(),
+        }
+    }
+
+    pub fn intersection(&self, other: &AnswerSet) -> AnswerSet {
+        AnswerSet {
+            ascii: self.ascii & other.ascii,
+            other: self.other.intersection(&other.other).copied().collect(),
+        }
+    }
+
+    pub fn difference(&self, other: &AnswerSet) -> AnswerSet {
+        AnswerSet {
+            ascii: self.ascii & !other.ascii,
+            other: self.other.difference(&other.other).copied().collect(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = char> + '_ {
+        let mut other: Vec<char> = self.other.iter().copied().collect();
+        other.sort_unstable();
+
+        (0..128u8)
+            .filter(move |&i| self.ascii & (1 << i) != 0)
+            .map(char::from)
+            .chain(other)
+    }
+}
+
+impl FromIterator<char> for AnswerSet {
+    fn from_iter<I: IntoIterator<Item = char>>(iter: I) -> Self {
+        let mut set = AnswerSet::default();
+
+        for c in iter {
+            set.insert(c);
+        }
+
+        set
+    }
+}
+
+impl fmt::Display for AnswerSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.iter().try_for_each(|c| write!(f, "{}", c))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Group {
+    line: usize,
+    members: Vec<AnswerSet>,
+}
+
+impl Group {
+    pub fn parse(record: &Record) -> Self {
+        let members = record
+            .lines
+            .iter()
+            .map(|line| line.text.chars().filter(|c| !c.is_whitespace()).collect())
+            .collect();
+
+        Group {
+            line: record.first_line(),
+            members,
+        }
+    }
+
+    pub fn anyone(&self) -> AnswerSet {
+        self.members
+            .iter()
+            .fold(AnswerSet::default(), |acc, member| acc.union(member))
+    }
+
+    pub fn everyone(&self) -> AnswerSet {
+        match self.members.split_first() {
+            Some((first, rest)) => rest
+                .iter()
+                .fold(first.clone(), |acc, member| acc.intersection(member)),
+            None => AnswerSet::default(),
+        }
+    }
+
+    pub fn counts(&self) -> Vec<(char, usize)> {
+        let mut ascii = [0; 128];
+        let mut other: HashMap<char, usize> = HashMap::new();
+
+        for member in &self.members {
+            let mut bits = member.ascii;
+
+            while bits != 0 {
+                ascii[bits.trailing_zeros() as usize] += 1;
+                bits &= bits - 1;
+            }
+
+            for &c in &member.other {
+                *other.entry(c).or_insert(0) += 1;
             }
         }
 
-        result += bytecount::naive_count_32(&responses, num_responders);
+        let mut counts: Vec<(char, usize)> = (0..128u8)
+            .filter(|&i| ascii[i as usize] > 0)
+            .map(|i| (char::from(i), ascii[i as usize]))
+            .chain(other)
+            .collect();
+
+        counts.sort_unstable();
+
+        counts
+    }
+
+    pub fn answered_by_exactly(&self, k: usize) -> AnswerSet {
+        self.counts()
+            .into_iter()
+            .filter(|&(_, count)| count == k)
+            .map(|(c, _)| c)
+            .collect()
+    }
+
+    pub fn answered_by_at_least(&self, fraction: f64) -> AnswerSet {
+        let needed = (fraction * self.members.len() as f64).ceil() as usize;
+
+        self.counts()
+            .into_iter()
+            .filter(|&(_, count)| count >= needed.max(1))
+            .map(|(c, _)| c)
+            .collect()
+    }
+
+    pub fn answered_by_nobody(&self, questions: &AnswerSet) -> AnswerSet {
+        questions.difference(&self.anyone())
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+struct Frequency {
+    people: usize,
+    groups: usize,
+}
+
+fn frequencies(groups: &[Group]) -> BTreeMap<char, Frequency> {
+    let mut frequencies: BTreeMap<char, Frequency> = BTreeMap::new();
+
+    for group in groups {
+        for (question, count) in group.counts() {
+            let frequency = frequencies.entry(question).or_default();
+
+            frequency.people += count;
+            frequency.groups += 1;
+        }
+    }
+
+    frequencies
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Query {
+    Exactly(usize),
+    AtLeast(f64),
+    Nobody(AnswerSet),
+}
+
+impl Query {
+    pub fn parse(arg: &str) -> Option<Query> {
+        let (name, value) = arg.strip_prefix("--")?.split_once('=')?;
+
+        match name {
+            "exactly" => Some(Query::Exactly(value.parse().unwrap())),
+            "at-least" => Some(Query::AtLeast(value.parse().unwrap())),
+            "nobody" => Some(Query::Nobody(value.chars().collect())),
+            _ => None,
+        }
+    }
+
+    pub fn answers(&self, group: &Group) -> AnswerSet {
+        match self {
+            Query::Exactly(k) => group.answered_by_exactly(*k),
+            Query::AtLeast(fraction) => group.answered_by_at_least(*fraction),
+            Query::Nobody(questions) => group.answered_by_nobody(questions),
+        }
     }
 
-    result
+    pub fn describe(&self) -> String {
+        match self {
+            Query::Exactly(k) => format!("Answered by exactly {} people", k),
+            Query::AtLeast(fraction) => format!("Answered by at least {} of the group", fraction),
+            Query::Nobody(questions) => format!("Answered by nobody, out of {}", questions),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -89,4 +304,78 @@ b";
     fn sample_input_part_two() {
         assert_eq!(part_two(TEST_INPUT.trim()), 6);
     }
+
+    #[test]
+    fn any_alphabet() {
+        let input = "aB1\r\nB1é\r\n\r\n\r\nxé ü\nüé\n\n\n";
+
+        let groups = parse_groups(input);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].members.len(), 2);
+        assert_eq!(groups[1].members.len(), 2);
+        assert_eq!(groups[0].anyone().len(), 4);
+        assert_eq!(groups[0].everyone(), "B1".chars().collect());
+        assert_eq!(groups[1].everyone(), "éü".chars().collect());
+        assert_eq!(part_one(input), 7);
+        assert_eq!(part_two(input), 4);
+    }
+
+    #[test]
+    fn trailing_newlines_do_not_add_members() {
+        assert_eq!(part_two("ab\nab\n"), 2);
+        assert_eq!(part_two("ab\r\nab\r\n\r\n"), 2);
+    }
+
+    #[test]
+    fn set_queries() {
+        let groups = parse_groups(TEST_INPUT);
+
+        let total = |query: Query| -> usize { groups.iter().map(|g| query.answers(g).len()).sum() };
+
+        assert_eq!(total(Query::Exactly(1)), 9);
+        assert_eq!(total(Query::Exactly(4)), 1);
+        assert_eq!(total(Query::AtLeast(0.5)), 8);
+        assert_eq!(total(Query::AtLeast(1.0)), part_two(TEST_INPUT));
+
+        let nobody: Vec<String> = groups
+            .iter()
+            .map(|g| g.answered_by_nobody(&"abcd".chars().collect()).to_string())
+            .collect();
+
+        assert_eq!(nobody, vec!["d", "d", "d", "bcd", "acd"]);
+        assert!(groups[3]
+            .answered_by_nobody(&"a".chars().collect())
+            .iter()
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn question_frequencies() {
+        let frequencies = frequencies(&parse_groups(TEST_INPUT));
+
+        assert_eq!(
+            frequencies[&'a'],
+            Frequency {
+                people: 8,
+                groups: 4
+            }
+        );
+        assert_eq!(
+            frequencies[&'b'],
+            Frequency {
+                people: 4,
+                groups: 4
+            }
+        );
+        assert_eq!(
+            frequencies[&'c'],
+            Frequency {
+                people: 3,
+                groups: 3
+            }
+        );
+        assert_eq!(frequencies.len(), 3);
+    }
 }
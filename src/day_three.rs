@@ -1,4 +1,7 @@
-use super::utils::start_day;
+use super::utils::{read_input_file, start_day};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::ops::RangeInclusive;
 
 pub fn main() {
     let input = start_day("three");
@@ -10,10 +13,63 @@ pub fn main() {
     println!();
 }
 
+pub fn render(args: &[String]) {
+    let mut slopes: Vec<Slope> = args
+        .iter()
+        .filter(|a| !a.starts_with("--"))
+        .map(|a| Slope::parse(a))
+        .collect();
+
+    if slopes.is_empty() {
+        slopes.push(Slope::steps(3, 1));
+    }
+
+    let edges = args
+        .iter()
+        .find_map(|a| a.strip_prefix("--edges="))
+        .map_or(Edges::Wrap, Edges::parse);
+
+    let map = Map::parse(&read_input_file("three"));
+    let overlay = map.overlay_paths(&slopes, edges);
+
+    if args.iter().any(|a| a == "--ppm") {
+        print!("{}", overlay.to_ppm());
+    } else if args.iter().any(|a| a == "--svg") {
+        print!("{}", overlay.to_svg());
+    } else {
+        print!("{}", overlay.to_text());
+    }
+}
+
+pub fn slopes(args: &[String]) {
+    let max_step_x = args.first().map_or(7, |x| x.parse().unwrap());
+    let max_step_y = args.get(1).map_or(2, |y| y.parse().unwrap());
+
+    let map = Map::parse(&read_input_file("three"));
+
+    let (xs, ys) = (1..=max_step_x, 1..=max_step_y);
+
+    for ((step_x, step_y), trees_hit) in map.tree_counts(xs.clone(), ys.clone()) {
+        println!("Right {}, down {}: {} trees", step_x, step_y, trees_hit);
+    }
+
+    if let Some(((step_x, step_y), trees_hit)) = map.fewest_trees(xs.clone(), ys.clone()) {
+        println!(
+            "Fewest trees: right {}, down {} ({} trees)",
+            step_x, step_y, trees_hit
+        );
+    }
+
+    if let Some(((step_x, step_y), trees_hit)) = map.most_trees(xs, ys) {
+        println!(
+            "Most trees: right {}, down {} ({} trees)",
+            step_x, step_y, trees_hit
+        );
+    }
+}
+
 fn part_one(map: &Map) -> usize {
-    map.toboggan_path(3, 1)
-        .filter(|&point| point == Point::Tree)
-        .count()
+    map.trees_hit(3, 1)
 }
 
 fn part_two(map: &Map) -> usize {
@@ -22,12 +78,7 @@ fn part_two(map: &Map) -> usize {
     let mut result = 1;
 
     for &(step_x, step_y) in &toboggans {
-        let trees_hit = map
-            .toboggan_path(step_x, step_y)
-            .filter(|&point| point == Point::Tree)
-            .count();
-
-        result *= trees_hit;
+        result *= map.trees_hit(step_x, step_y);
     }
 
     result
@@ -64,13 +115,204 @@ impl Map {
         Map { grid }
     }
 
+    fn width(&self) -> usize {
+        self.grid.first().map_or(0, |line| line.len())
+    }
+
     pub fn toboggan_path(&self, step_x: usize, step_y: usize) -> TobogganIterator {
+        self.traverse(Slope::steps(step_x as isize, step_y as isize), Edges::Wrap)
+    }
+
+    pub fn traverse(&self, slope: Slope, edges: Edges) -> TobogganIterator<'_> {
+        let start_x = if slope.right < 0 {
+            self.width().saturating_sub(1)
+        } else {
+            0
+        };
+        let start_y = if slope.down < 0 {
+            self.grid.len().saturating_sub(1)
+        } else {
+            0
+        };
+
         TobogganIterator {
             map: self,
-            x: 0,
-            y: 0,
-            step_x,
-            step_y,
+            start: (start_x as isize, start_y as isize),
+            slope,
+            edges,
+            step: 0,
+        }
+    }
+
+    pub fn trees_hit(&self, step_x: usize, step_y: usize) -> usize {
+        self.toboggan_path(step_x, step_y)
+            .filter(|&point| point == Point::Tree)
+            .count()
+    }
+
+    pub fn tree_counts(
+        &self,
+        xs: RangeInclusive<usize>,
+        ys: RangeInclusive<usize>,
+    ) -> Vec<((usize, usize), usize)> {
+        let width = self.width().max(1);
+        let height = self.grid.len().max(1);
+
+        // Paths repeat every `width` steps across and all stop after the first
+        // point once a step down leaves the map, so each distinct path is only
+        // walked once.
+        let mut trees_per_path = HashMap::new();
+
+        let mut counts = Vec::new();
+
+        for step_y in ys.filter(|&y| y > 0) {
+            for step_x in xs.clone() {
+                let path = (step_x % width, step_y.min(height));
+
+                let trees_hit = *trees_per_path
+                    .entry(path)
+                    .or_insert_with(|| self.trees_hit(path.0, path.1));
+
+                counts.push(((step_x, step_y), trees_hit));
+            }
+        }
+
+        counts
+    }
+
+    pub fn overlay_paths(&self, slopes: &[Slope], edges: Edges) -> PathOverlay {
+        let width = self.width() as isize;
+        let height = self.grid.len() as isize;
+
+        let mut visited = Vec::new();
+        let (mut min_x, mut max_x) = (0, 0);
+
+        for &slope in slopes {
+            let mut path = self.traverse(slope, edges);
+
+            while let Some(((x, y), point)) = path.next_with_position() {
+                let (x, y) = match edges {
+                    Edges::Torus => (x.rem_euclid(width), y.rem_euclid(height)),
+                    _ => (x, y),
+                };
+
+                min_x = min_x.min(x);
+                max_x = max_x.max(x);
+                visited.push(((x, y), point));
+            }
+        }
+
+        if width == 0 {
+            return PathOverlay { squares: vec![] };
+        }
+
+        let first_tile = min_x.div_euclid(width);
+        let repeats = (max_x.div_euclid(width) - first_tile + 1) as usize;
+
+        let mut squares: Vec<Vec<Square>> = self
+            .grid
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .cycle()
+                    .take(line.len() * repeats)
+                    .map(|&point| Square::Unvisited(point))
+                    .collect()
+            })
+            .collect();
+
+        for ((x, y), point) in visited {
+            squares[y as usize][(x - first_tile * width) as usize] = Square::Visited(point);
+        }
+
+        PathOverlay { squares }
+    }
+
+    pub fn fewest_trees(
+        &self,
+        xs: RangeInclusive<usize>,
+        ys: RangeInclusive<usize>,
+    ) -> Option<((usize, usize), usize)> {
+        self.tree_counts(xs, ys)
+            .into_iter()
+            .min_by_key(|&(_, trees_hit)| trees_hit)
+    }
+
+    pub fn most_trees(
+        &self,
+        xs: RangeInclusive<usize>,
+        ys: RangeInclusive<usize>,
+    ) -> Option<((usize, usize), usize)> {
+        self.tree_counts(xs, ys)
+            .into_iter()
+            .rev()
+            .max_by_key(|&(_, trees_hit)| trees_hit)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Slope {
+    right: isize,
+    down: isize,
+    interpolate: bool,
+}
+
+impl Slope {
+    pub fn steps(right: isize, down: isize) -> Self {
+        Slope {
+            right,
+            down,
+            interpolate: false,
+        }
+    }
+
+    pub fn ratio(right: isize, down: isize) -> Self {
+        Slope {
+            right,
+            down,
+            interpolate: true,
+        }
+    }
+
+    pub fn parse(text: &str) -> Self {
+        if let Some(end) = text.find('/') {
+            Slope::ratio(
+                text[..end].parse().unwrap(),
+                text[end + 1..].parse().unwrap(),
+            )
+        } else {
+            let end = text.find(',').unwrap();
+
+            Slope::steps(
+                text[..end].parse().unwrap(),
+                text[end + 1..].parse().unwrap(),
+            )
+        }
+    }
+
+    fn sub_steps(&self) -> isize {
+        if self.interpolate {
+            self.right.abs().max(self.down.abs()).max(1)
+        } else {
+            1
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Edges {
+    Wrap,
+    Torus,
+    Stop,
+}
+
+impl Edges {
+    pub fn parse(text: &str) -> Self {
+        match text {
+            "wrap" => Edges::Wrap,
+            "torus" => Edges::Torus,
+            "stop" => Edges::Stop,
+            _ => panic!("Unknown edge behaviour: {}", text),
         }
     }
 }
@@ -78,24 +320,157 @@ impl Map {
 #[derive(Debug, Copy, Clone)]
 struct TobogganIterator<'a> {
     map: &'a Map,
-    x: usize,
-    y: usize,
-    step_x: usize,
-    step_y: usize,
+    start: (isize, isize),
+    slope: Slope,
+    edges: Edges,
+    step: isize,
+}
+
+impl<'a> TobogganIterator<'a> {
+    pub fn next_with_position(&mut self) -> Option<((isize, isize), Point)> {
+        let width = self.map.width() as isize;
+        let height = self.map.grid.len() as isize;
+
+        if width == 0 {
+            return None;
+        }
+
+        let sub_steps = self.slope.sub_steps();
+        let dx = self.step * self.slope.right / sub_steps;
+        let dy = self.step * self.slope.down / sub_steps;
+
+        // Once a wrapping path is back where it started it just repeats itself
+        let back_at_start = match self.edges {
+            Edges::Wrap => dx % width == 0 && dy == 0,
+            Edges::Torus => dx % width == 0 && dy % height == 0,
+            Edges::Stop => dx == 0 && dy == 0,
+        };
+
+        if self.step > 0 && self.step % sub_steps == 0 && back_at_start {
+            return None;
+        }
+
+        let (x, y) = (self.start.0 + dx, self.start.1 + dy);
+
+        let (grid_x, grid_y) = match self.edges {
+            Edges::Wrap => (x.rem_euclid(width), y),
+            Edges::Torus => (x.rem_euclid(width), y.rem_euclid(height)),
+            Edges::Stop => (x, y),
+        };
+
+        if grid_x < 0 || grid_x >= width || grid_y < 0 || grid_y >= height {
+            return None;
+        }
+
+        self.step += 1;
+
+        Some(((x, y), self.map.grid[grid_y as usize][grid_x as usize]))
+    }
 }
 
 impl<'a> Iterator for TobogganIterator<'a> {
     type Item = Point;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let line = self.map.grid.get(self.y)?;
+        self.next_with_position().map(|(_, point)| point)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Square {
+    Unvisited(Point),
+    Visited(Point),
+}
+
+impl Square {
+    pub fn to_char(self) -> char {
+        match self {
+            Square::Unvisited(Point::Empty) => '.',
+            Square::Unvisited(Point::Tree) => '#',
+            Square::Visited(Point::Empty) => 'O',
+            Square::Visited(Point::Tree) => 'X',
+        }
+    }
+
+    pub fn colour(self) -> (u8, u8, u8) {
+        match self {
+            Square::Unvisited(Point::Empty) => (255, 255, 255),
+            Square::Unvisited(Point::Tree) => (34, 139, 34),
+            Square::Visited(Point::Empty) => (30, 144, 255),
+            Square::Visited(Point::Tree) => (220, 20, 60),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PathOverlay {
+    squares: Vec<Vec<Square>>,
+}
+
+impl PathOverlay {
+    fn width(&self) -> usize {
+        self.squares.first().map_or(0, |row| row.len())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        for row in &self.squares {
+            out.extend(row.iter().map(|square| square.to_char()));
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn to_ppm(&self) -> String {
+        let mut out = format!("P3\n{} {}\n255\n", self.width(), self.squares.len());
+
+        for row in &self.squares {
+            let pixels: Vec<String> = row
+                .iter()
+                .map(|square| {
+                    let (r, g, b) = square.colour();
+
+                    format!("{} {} {}", r, g, b)
+                })
+                .collect();
+
+            writeln!(out, "{}", pixels.join(" ")).unwrap();
+        }
 
-        let x = self.x % line.len();
+        out
+    }
+
+    pub fn to_svg(&self) -> String {
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" shape-rendering=\"crispEdges\">\n",
+            self.width(),
+            self.squares.len()
+        );
+
+        writeln!(out, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>").unwrap();
 
-        self.x += self.step_x;
-        self.y += self.step_y;
+        for (y, row) in self.squares.iter().enumerate() {
+            for (x, &square) in row.iter().enumerate() {
+                if square == Square::Unvisited(Point::Empty) {
+                    continue;
+                }
+
+                let (r, g, b) = square.colour();
+
+                writeln!(
+                    out,
+                    "<rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\" fill=\"rgb({},{},{})\"/>",
+                    x, y, r, g, b
+                )
+                .unwrap();
+            }
+        }
 
-        Some(line[x])
+        out.push_str("</svg>\n");
+
+        out
     }
 }
 
@@ -130,4 +505,89 @@ mod tests {
 
         assert_eq!(part_two(&map), 336);
     }
+
+    #[test]
+    fn tree_counts_match_individual_paths() {
+        let map = Map::parse(TEST_INPUT.trim());
+
+        for ((step_x, step_y), trees_hit) in map.tree_counts(0..=30, 1..=12) {
+            assert_eq!(trees_hit, map.trees_hit(step_x, step_y));
+        }
+    }
+
+    #[test]
+    fn render_path_as_text() {
+        let map = Map::parse(TEST_INPUT.trim());
+
+        let expected = "
+O.##.........##.........##.......
+#..O#...#..#...#...#..#...#...#..
+.#....X..#..#....#..#..#....#..#.
+..#.#...#O#..#.#...#.#..#.#...#.#
+.#...##..#..X...##..#..#...##..#.
+..#.##.......#.X#.......#.##.....
+.#.#.#....#.#.#.#.O..#.#.#.#....#
+.#........#.#........X.#........#
+#.##...#...#.##...#...#.X#...#...
+#...##....##...##....##...#X....#
+.#..#...#.#.#..#...#.#.#..#...X.#
+";
+
+        assert_eq!(
+            map.overlay_paths(&[Slope::steps(3, 1)], Edges::Wrap)
+                .to_text(),
+            expected.trim_start()
+        );
+    }
+
+    #[test]
+    fn signed_steps_and_edges() {
+        let map = Map::parse(TEST_INPUT.trim());
+
+        let trees = |slope, edges| {
+            map.traverse(slope, edges)
+                .filter(|&point| point == Point::Tree)
+                .count()
+        };
+
+        assert_eq!(trees(Slope::steps(3, 1), Edges::Wrap), 7);
+        assert_eq!(trees(Slope::steps(-3, -1), Edges::Wrap), 3);
+        assert_eq!(trees(Slope::steps(3, 1), Edges::Stop), 1);
+        assert_eq!(map.traverse(Slope::steps(3, 1), Edges::Stop).count(), 4);
+        assert_eq!(map.traverse(Slope::steps(1, 0), Edges::Wrap).count(), 11);
+        assert_eq!(map.traverse(Slope::steps(1, 1), Edges::Torus).count(), 11);
+        assert_eq!(map.traverse(Slope::steps(3, 2), Edges::Torus).count(), 11);
+
+        let empty = Map::parse("");
+
+        for &edges in &[Edges::Wrap, Edges::Torus, Edges::Stop] {
+            assert_eq!(empty.traverse(Slope::steps(-1, -1), edges).count(), 0);
+        }
+    }
+
+    #[test]
+    fn interpolated_slopes() {
+        let map = Map::parse(TEST_INPUT.trim());
+
+        let positions: Vec<_> = std::iter::from_fn({
+            let mut path = map.traverse(Slope::ratio(1, 2), Edges::Wrap);
+
+            move || path.next_with_position().map(|(position, _)| position)
+        })
+        .take(5)
+        .collect();
+
+        assert_eq!(positions, vec![(0, 0), (0, 1), (1, 2), (1, 3), (2, 4)]);
+        assert_eq!(map.traverse(Slope::ratio(1, 2), Edges::Wrap).count(), 11);
+        assert_eq!(map.traverse(Slope::ratio(3, 1), Edges::Stop).count(), 11);
+    }
+
+    #[test]
+    fn fewest_and_most_trees() {
+        let map = Map::parse(TEST_INPUT.trim());
+
+        assert_eq!(map.fewest_trees(1..=7, 1..=2), Some(((5, 2), 0)));
+        assert_eq!(map.most_trees(1..=7, 1..=2), Some(((3, 1), 7)));
+        assert_eq!(map.most_trees(1..=7, 0..=0), None);
+    }
 }
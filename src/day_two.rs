@@ -1,4 +1,7 @@
-use super::utils::{get_text_up_to, start_day};
+use super::json;
+use super::utils::{read_input_arg, start_day};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
 
 pub fn main() {
     let input = start_day("two");
@@ -8,6 +11,18 @@ pub fn main() {
     println!();
 }
 
+pub fn audit(args: &[String]) {
+    let input = read_input_arg(args, "two");
+
+    let report = AuditReport::of(&input);
+
+    if args.iter().any(|a| a == "--json") {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report.to_text());
+    }
+}
+
 fn part_one(input: &str) -> usize {
     input
         .lines()
@@ -34,40 +49,307 @@ struct PasswordEntry<'a> {
 
 impl<'a> PasswordEntry<'a> {
     pub fn parse(line: &'a str) -> Self {
-        let (min_occurs, line) = get_text_up_to(line, '-');
-        let (max_occurs, line) = get_text_up_to(line, ' ');
-        let letter = line.chars().next().unwrap();
+        Self::try_parse(line).expect("Failed to parse password entry")
+    }
+
+    pub fn try_parse(line: &'a str) -> Option<Self> {
+        let (range, line) = line.split_once(' ')?;
+        let (min_occurs, max_occurs) = range.split_once('-')?;
+        let (letter, password) = line.split_once(": ")?;
 
-        let password = &line[3..];
+        let mut letters = letter.chars();
+        let letter = letters.next().filter(|_| letters.next().is_none())?;
 
-        PasswordEntry {
-            min_occurs: min_occurs.parse().unwrap(),
-            max_occurs: max_occurs.parse().unwrap(),
+        Some(PasswordEntry {
+            min_occurs: min_occurs.parse().ok()?,
+            max_occurs: max_occurs.parse().ok()?,
             letter,
             password,
-        }
+        })
+    }
+
+    pub fn occurrences(&self) -> usize {
+        self.password.chars().filter(|&c| c == self.letter).count()
+    }
+
+    pub fn position_hits(&self) -> (bool, bool) {
+        (
+            self.has_letter_at(self.min_occurs),
+            self.has_letter_at(self.max_occurs),
+        )
+    }
+
+    fn has_letter_at(&self, position: usize) -> bool {
+        position
+            .checked_sub(1)
+            .and_then(|i| self.password.chars().nth(i))
+            == Some(self.letter)
     }
 
     pub fn is_valid_v1(&self) -> bool {
-        let count = self.password.chars().filter(|&c| c == self.letter).count();
+        let count = self.occurrences();
 
         count >= self.min_occurs && count <= self.max_occurs
     }
 
     pub fn is_valid_v2(&self) -> bool {
-        let mut chars = self.password.chars();
+        let (a, b) = self.position_hits();
+
+        a != b
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct AuditEntry<'a> {
+    line: usize,
+    entry: PasswordEntry<'a>,
+    occurrences: usize,
+    position_hits: (bool, bool),
+}
+
+impl<'a> AuditEntry<'a> {
+    pub fn of(line: usize, entry: PasswordEntry<'a>) -> Self {
+        AuditEntry {
+            line,
+            entry,
+            occurrences: entry.occurrences(),
+            position_hits: entry.position_hits(),
+        }
+    }
+
+    pub fn is_valid(&self, policy: Policy) -> bool {
+        match policy {
+            Policy::V1 => self.entry.is_valid_v1(),
+            Policy::V2 => self.entry.is_valid_v2(),
+        }
+    }
+}
 
-        let a = chars.nth(self.min_occurs - 1).unwrap();
-        let b = chars.nth(self.max_occurs - (self.min_occurs + 1)).unwrap();
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Policy {
+    V1,
+    V2,
+}
 
-        if a == self.letter {
-            b != self.letter
-        } else {
-            b == self.letter
+impl Policy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Policy::V1 => "v1",
+            Policy::V2 => "v2",
         }
     }
 }
 
+#[derive(Debug)]
+struct AuditReport<'a> {
+    entries: Vec<AuditEntry<'a>>,
+    unparseable: Vec<(usize, &'a str)>,
+}
+
+impl<'a> AuditReport<'a> {
+    pub fn of(input: &'a str) -> Self {
+        let mut entries = Vec::new();
+        let mut unparseable = Vec::new();
+
+        for (i, l) in input.lines().enumerate() {
+            if l.trim().is_empty() {
+                continue;
+            }
+
+            match PasswordEntry::try_parse(l) {
+                Some(entry) => entries.push(AuditEntry::of(i + 1, entry)),
+                None => unparseable.push((i + 1, l)),
+            }
+        }
+
+        AuditReport {
+            entries,
+            unparseable,
+        }
+    }
+
+    pub fn num_valid(&self, policy: Policy) -> usize {
+        self.entries.iter().filter(|e| e.is_valid(policy)).count()
+    }
+
+    pub fn failing_letters(&self, policy: Policy) -> Vec<(char, usize)> {
+        let mut counts = HashMap::new();
+
+        for entry in self.entries.iter().filter(|e| !e.is_valid(policy)) {
+            *counts.entry(entry.entry.letter).or_insert(0) += 1;
+        }
+
+        let mut counts: Vec<_> = counts.into_iter().collect();
+
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        counts
+    }
+
+    pub fn range_distribution(&self) -> BTreeMap<(usize, usize), usize> {
+        let mut ranges = BTreeMap::new();
+
+        for e in &self.entries {
+            *ranges
+                .entry((e.entry.min_occurs, e.entry.max_occurs))
+                .or_insert(0) += 1;
+        }
+
+        ranges
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        for e in &self.entries {
+            let PasswordEntry {
+                min_occurs,
+                max_occurs,
+                letter,
+                password,
+            } = e.entry;
+
+            writeln!(
+                out,
+                "{:>5}  {}-{} {}: {}  v1: {} ({} occurrences)  v2: {} (positions {}/{}: {}/{})",
+                e.line,
+                min_occurs,
+                max_occurs,
+                letter,
+                password,
+                validity(e.is_valid(Policy::V1)),
+                e.occurrences,
+                validity(e.is_valid(Policy::V2)),
+                min_occurs,
+                max_occurs,
+                hit_or_miss(e.position_hits.0),
+                hit_or_miss(e.position_hits.1),
+            )
+            .unwrap();
+        }
+
+        for (line, text) in &self.unparseable {
+            writeln!(out, "{:>5}  unparseable: {}", line, text).unwrap();
+        }
+
+        writeln!(out).unwrap();
+        writeln!(out, "Entries: {}", self.entries.len()).unwrap();
+        writeln!(out, "Unparseable: {}", self.unparseable.len()).unwrap();
+        writeln!(out, "Valid (v1): {}", self.num_valid(Policy::V1)).unwrap();
+        writeln!(out, "Valid (v2): {}", self.num_valid(Policy::V2)).unwrap();
+
+        for &policy in &[Policy::V1, Policy::V2] {
+            let letters: Vec<String> = self
+                .failing_letters(policy)
+                .iter()
+                .take(5)
+                .map(|(letter, count)| format!("{} ({})", letter, count))
+                .collect();
+
+            writeln!(
+                out,
+                "Most common failing letters ({}): {}",
+                policy.name(),
+                letters.join(", ")
+            )
+            .unwrap();
+        }
+
+        writeln!(out, "Range distribution:").unwrap();
+
+        for ((min, max), count) in self.range_distribution() {
+            writeln!(out, "  {}-{}: {}", min, max, count).unwrap();
+        }
+
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|e| {
+                format!(
+                    "{{\"line\":{},\"min\":{},\"max\":{},\"letter\":{},\"password\":{},\
+                     \"v1\":{{\"valid\":{},\"occurrences\":{}}},\
+                     \"v2\":{{\"valid\":{},\"position_hits\":[{},{}]}}}}",
+                    e.line,
+                    e.entry.min_occurs,
+                    e.entry.max_occurs,
+                    json::string(&e.entry.letter.to_string()),
+                    json::string(e.entry.password),
+                    e.is_valid(Policy::V1),
+                    e.occurrences,
+                    e.is_valid(Policy::V2),
+                    e.position_hits.0,
+                    e.position_hits.1,
+                )
+            })
+            .collect();
+
+        let unparseable: Vec<String> = self
+            .unparseable
+            .iter()
+            .map(|(line, text)| format!("{{\"line\":{},\"text\":{}}}", line, json::string(text)))
+            .collect();
+
+        let mut summary = format!(
+            "\"entries\":{},\"unparseable\":{},\"valid_v1\":{},\"valid_v2\":{}",
+            self.entries.len(),
+            self.unparseable.len(),
+            self.num_valid(Policy::V1),
+            self.num_valid(Policy::V2),
+        );
+
+        for &policy in &[Policy::V1, Policy::V2] {
+            let letters: Vec<String> = self
+                .failing_letters(policy)
+                .iter()
+                .map(|(letter, count)| format!("[{},{}]", json::string(&letter.to_string()), count))
+                .collect();
+
+            write!(
+                summary,
+                ",\"failing_letters_{}\":[{}]",
+                policy.name(),
+                letters.join(",")
+            )
+            .unwrap();
+        }
+
+        let ranges: Vec<String> = self
+            .range_distribution()
+            .iter()
+            .map(|((min, max), count)| format!("\"{}-{}\":{}", min, max, count))
+            .collect();
+
+        write!(summary, ",\"ranges\":{{{}}}", ranges.join(",")).unwrap();
+
+        format!(
+            "{{\"entries\":[{}],\"unparseable\":[{}],\"summary\":{{{}}}}}",
+            entries.join(","),
+            unparseable.join(","),
+            summary
+        )
+    }
+}
+
+fn validity(valid: bool) -> &'static str {
+    if valid {
+        "valid"
+    } else {
+        "invalid"
+    }
+}
+
+fn hit_or_miss(hit: bool) -> &'static str {
+    if hit {
+        "hit"
+    } else {
+        "miss"
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -85,4 +367,53 @@ mod tests {
     fn sample_input_part_two() {
         assert_eq!(part_two(TEST_INPUT), 1);
     }
+
+    #[test]
+    fn audit_report_summary() {
+        let report = AuditReport::of(TEST_INPUT);
+
+        assert_eq!(report.num_valid(Policy::V1), 2);
+        assert_eq!(report.failing_letters(Policy::V1), vec![('b', 1)]);
+        assert_eq!(report.failing_letters(Policy::V2), vec![('b', 1), ('c', 1)]);
+
+        let ranges: Vec<_> = report.range_distribution().into_iter().collect();
+
+        assert_eq!(ranges, vec![((1, 3), 2), ((2, 9), 1)]);
+    }
+
+    #[test]
+    fn audit_report_json() {
+        let report = AuditReport::of("1-3 a: abcde");
+
+        assert_eq!(
+            report.to_json(),
+            "{\"entries\":[{\"line\":1,\"min\":1,\"max\":3,\"letter\":\"a\",\"password\":\"abcde\",\
+             \"v1\":{\"valid\":true,\"occurrences\":1},\
+             \"v2\":{\"valid\":true,\"position_hits\":[true,false]}}],\
+             \"unparseable\":[],\
+             \"summary\":{\"entries\":1,\"unparseable\":0,\"valid_v1\":1,\"valid_v2\":1,\
+             \"failing_letters_v1\":[],\"failing_letters_v2\":[],\"ranges\":{\"1-3\":1}}}"
+        );
+    }
+    #[test]
+    fn audit_report_lists_unparseable_lines() {
+        let report =
+            AuditReport::of("1-3 a: abcde\n1-3 a abcde\n\nx-3 b: bbb\n2-4 cd: ccc\n1-2 d: dd");
+
+        assert_eq!(report.entries.len(), 2);
+        assert_eq!(
+            report.unparseable,
+            vec![(2, "1-3 a abcde"), (4, "x-3 b: bbb"), (5, "2-4 cd: ccc")]
+        );
+
+        let text = report.to_text();
+
+        assert!(text.contains("    2  unparseable: 1-3 a abcde\n"));
+        assert!(text.contains("Unparseable: 3\n"));
+
+        assert!(report.to_json().contains(
+            "\"unparseable\":[{\"line\":2,\"text\":\"1-3 a abcde\"},\
+             {\"line\":4,\"text\":\"x-3 b: bbb\"},{\"line\":5,\"text\":\"2-4 cd: ccc\"}]"
+        ));
+    }
 }
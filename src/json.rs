@@ -0,0 +1,312 @@
+use std::fmt::{self, Write};
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid JSON at byte {}: {}", self.offset, self.message)
+    }
+}
+
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                write!(out, "\\u{:04x}", c as u32).unwrap();
+            }
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+
+    out
+}
+
+pub fn parse(text: &str) -> Result<Value, Error> {
+    let mut parser = Parser {
+        chars: text.char_indices().peekable(),
+        len: text.len(),
+    };
+
+    let value = parser.value()?;
+
+    parser.skip_whitespace();
+
+    match parser.chars.peek() {
+        Some(&(_, c)) => Err(parser.error(format!("unexpected trailing {:?}", c))),
+        None => Ok(value),
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    len: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&mut self, message: String) -> Error {
+        Error {
+            offset: self.chars.peek().map_or(self.len, |&(i, _)| i),
+            message,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        self.chars.next().map(|(_, c)| c)
+    }
+
+    fn value(&mut self) -> Result<Value, Error> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('{') => {
+                self.next();
+
+                let mut fields = Vec::new();
+
+                self.for_each_item('}', |parser| {
+                    parser.skip_whitespace();
+                    parser.expect('"')?;
+
+                    let key = parser.string()?;
+
+                    parser.skip_whitespace();
+                    parser.expect(':')?;
+
+                    fields.push((key, parser.value()?));
+
+                    Ok(())
+                })?;
+
+                Ok(Value::Object(fields))
+            }
+            Some('[') => {
+                self.next();
+
+                let mut items = Vec::new();
+
+                self.for_each_item(']', |parser| {
+                    items.push(parser.value()?);
+
+                    Ok(())
+                })?;
+
+                Ok(Value::Array(items))
+            }
+            Some('"') => {
+                self.next();
+
+                Ok(Value::String(self.string()?))
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let mut number = String::new();
+
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E') {
+                        number.push(c);
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                match number.parse::<f64>() {
+                    Ok(_) => Ok(Value::Number(number)),
+                    Err(_) => Err(self.error(format!("{:?} is not a number", number))),
+                }
+            }
+            Some(_) => {
+                let mut word = String::new();
+
+                while let Some(c) = self.peek() {
+                    if c.is_ascii_alphabetic() {
+                        word.push(c);
+                        self.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                match word.as_str() {
+                    "null" => Ok(Value::Null),
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "" => {
+                        let c = self.peek();
+
+                        Err(self.error(format!("unexpected {:?}", c.unwrap())))
+                    }
+                    _ => Err(self.error(format!("unexpected value {:?}", word))),
+                }
+            }
+            None => Err(self.error("unexpected end of input".to_string())),
+        }
+    }
+
+    fn for_each_item<F>(&mut self, close: char, mut parse_item: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut Self) -> Result<(), Error>,
+    {
+        self.skip_whitespace();
+
+        if self.peek() == Some(close) {
+            self.next();
+            return Ok(());
+        }
+
+        loop {
+            parse_item(self)?;
+
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some(',') => {}
+                Some(c) if c == close => {
+                    self.next();
+                    return Ok(());
+                }
+                c => return Err(self.error(format!("expected ',' or '{}', got {:?}", close, c))),
+            }
+
+            self.next();
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let mut s = String::new();
+
+        loop {
+            match self.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.next()).collect();
+
+                        let code = u32::from_str_radix(&hex, 16)
+                            .map_err(|_| self.error(format!("invalid escape \\u{}", hex)))?;
+
+                        s.push(std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    Some(c) => s.push(c),
+                    None => return Err(self.error("unterminated string".to_string())),
+                },
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string".to_string())),
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|&(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next();
+                Ok(())
+            }
+            c => Err(self.error(format!("expected '{}', got {:?}", expected, c))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(string("abc"), "\"abc\"");
+        assert_eq!(string("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+    }
+
+    #[test]
+    fn parses_values() {
+        let value = parse(r#" [ {"a": "x\"y", "b": [1, -2.5e1, true, null]}, {} ] "#);
+
+        assert_eq!(
+            value,
+            Ok(Value::Array(vec![
+                Value::Object(vec![
+                    ("a".to_string(), Value::String("x\"y".to_string())),
+                    (
+                        "b".to_string(),
+                        Value::Array(vec![
+                            Value::Number("1".to_string()),
+                            Value::Number("-2.5e1".to_string()),
+                            Value::Bool(true),
+                            Value::Null
+                        ])
+                    ),
+                ]),
+                Value::Object(vec![]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let error = |text| parse(text).unwrap_err().to_string();
+
+        assert_eq!(
+            error(r#"[{"a": }]"#),
+            "invalid JSON at byte 7: unexpected '}'"
+        );
+        assert_eq!(
+            error("[1, 2"),
+            "invalid JSON at byte 5: expected ',' or ']', got None"
+        );
+        assert_eq!(
+            error("[1] 2"),
+            "invalid JSON at byte 4: unexpected trailing '2'"
+        );
+        assert_eq!(
+            error(r#"{"a" 1}"#),
+            "invalid JSON at byte 5: expected ':', got Some('1')"
+        );
+        assert_eq!(
+            error("nul"),
+            "invalid JSON at byte 3: unexpected value \"nul\""
+        );
+        assert_eq!(
+            error(r#""abc"#),
+            "invalid JSON at byte 4: unterminated string"
+        );
+    }
+}
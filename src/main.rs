@@ -18,11 +18,46 @@ mod day_three;
 mod day_twelve;
 mod day_two;
 
+mod csv;
+mod json;
 mod pairs;
 mod prime_factors;
+mod records;
 mod utils;
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        None => run_all_days(),
+        Some("password-audit") => day_two::audit(&args[1..]),
+        Some("toboggan-slopes") => day_three::slopes(&args[1..]),
+        Some("toboggan-render") => day_three::render(&args[1..]),
+        Some("passport-check") => day_four::check(&args[1..]),
+        Some("passport-export") => day_four::export(&args[1..]),
+        Some("passport-import") => day_four::import(&args[1..]),
+        Some("passport-normalise") => day_four::normalise(&args[1..]),
+        Some("boarding-pass") => day_five::boarding_pass(&args[1..]),
+        Some("missing-seat") => day_five::missing_seat(&args[1..]),
+        Some("seat-map") => day_five::seat_map(&args[1..]),
+        Some("customs-query") => day_six::query(&args[1..]),
+        Some("bag-check") => day_seven::check(&args[1..]),
+        Some("bag-query") => day_seven::query(&args[1..]),
+        Some("bag-dot") => day_seven::dot(&args[1..]),
+        Some("handheld-debug") => day_eight::debug(&args[1..]),
+        Some("handheld-run") => day_eight::run(&args[1..]),
+        Some("handheld-repair") => day_eight::repair(&args[1..]),
+        Some("handheld-cfg") => day_eight::cfg(&args[1..]),
+        Some("handheld-asm") => day_eight::assemble(&args[1..]),
+        Some("handheld-disasm") => day_eight::disassemble(&args[1..]),
+        Some(command) => {
+            eprintln!("Unknown command: {}", command);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_all_days() {
     day_one::main();
     day_two::main();
     day_three::main();
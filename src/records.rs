@@ -0,0 +1,114 @@
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Line<'a> {
+    pub number: usize,
+    pub text: &'a str,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record<'a> {
+    pub lines: Vec<Line<'a>>,
+}
+
+impl<'a> Record<'a> {
+    pub fn first_line(&self) -> usize {
+        self.lines.first().map_or(0, |line| line.number)
+    }
+
+    pub fn last_line(&self) -> usize {
+        self.lines.last().map_or(0, |line| line.number)
+    }
+
+    pub fn tokens(&self) -> impl Iterator<Item = Token<'a>> + '_ {
+        self.lines.iter().flat_map(|line| {
+            line.text.split_whitespace().map(move |word| {
+                let offset = word.as_ptr() as usize - line.text.as_ptr() as usize;
+
+                Token {
+                    text: word,
+                    line: line.number,
+                    column: line.text[..offset].chars().count() + 1,
+                }
+            })
+        })
+    }
+}
+
+pub fn blank_line_groups(input: &str) -> Vec<Record<'_>> {
+    let mut records = Vec::new();
+    let mut lines = Vec::new();
+
+    for (i, text) in input.lines().enumerate() {
+        if text.trim().is_empty() {
+            if !lines.is_empty() {
+                records.push(Record {
+                    lines: std::mem::take(&mut lines),
+                });
+            }
+        } else {
+            lines.push(Line {
+                number: i + 1,
+                text: text.trim_end(),
+            });
+        }
+    }
+
+    if !lines.is_empty() {
+        records.push(Record { lines });
+    }
+
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_with_crlf_and_whitespace_lines() {
+        let input = "a b\r\nc\r\n  \t\r\n\r\nd\n \ne  \n";
+
+        let groups: Vec<Vec<&str>> = blank_line_groups(input)
+            .iter()
+            .map(|record| record.lines.iter().map(|line| line.text).collect())
+            .collect();
+
+        assert_eq!(groups, vec![vec!["a b", "c"], vec!["d"], vec!["e"]]);
+    }
+
+    #[test]
+    fn token_positions() {
+        let records = blank_line_groups("\nab  cd\n\tef\n");
+
+        let tokens: Vec<_> = records[0].tokens().collect();
+
+        assert_eq!(records[0].first_line(), 2);
+        assert_eq!(records[0].last_line(), 3);
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    text: "ab",
+                    line: 2,
+                    column: 1
+                },
+                Token {
+                    text: "cd",
+                    line: 2,
+                    column: 5
+                },
+                Token {
+                    text: "ef",
+                    line: 3,
+                    column: 2
+                },
+            ]
+        );
+    }
+}
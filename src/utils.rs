@@ -13,6 +13,24 @@ pub fn read_input_file(day_number: &str) -> String {
     std::fs::read_to_string(file_name).unwrap()
 }
 
+pub fn read_input_arg(args: &[String], day_number: &str) -> String {
+    match path_arg(args) {
+        Some(path) => std::fs::read_to_string(path).unwrap(),
+        None => read_input_file(day_number),
+    }
+}
+
+pub fn path_arg(args: &[String]) -> Option<&str> {
+    args.iter()
+        .map(String::as_str)
+        .find(|a| !a.starts_with("--"))
+}
+
+pub fn option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .find_map(|a| a.strip_prefix("--")?.strip_prefix(name)?.strip_prefix('='))
+}
+
 pub fn parse_ints<'a, I, Err>(text: &'a str) -> impl Iterator<Item = I> + 'a
 where
     I: FromStr<Err = Err>,